@@ -0,0 +1,52 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+
+// a part of the puzzle with the legacy solver and the one replacing it
+pub type Part = (&'static str, Solver, Solver);
+pub type Solver = fn(&str) -> io::Result<u64>;
+
+// writes `count` generated `items` to a file in the temp directory, then
+// times both solvers of every part on it and flags any disagreement
+pub fn compare(
+  day: &str,
+  count: usize,
+  items: &str,
+  generate: fn(&str, usize) -> io::Result<()>,
+  labels: [&str; 2],
+  parts: &[Part],
+) -> io::Result<()> {
+  let path = env::temp_dir().join(format!("aoc2023-{}-bench-{}", day, count));
+  let file = path.to_str().unwrap();
+  generate(file, count)?;
+  println!("Generated {} {} in {}", count, items, file);
+
+  let result = parts.iter().try_for_each(|(name, legacy, new)| {
+    let (legacy, legacy_time) = time(|| legacy(file));
+    let (new, new_time) = time(|| new(file));
+    let (legacy, new) = (legacy?, new?);
+    println!(
+      "{}: {} {} in {:?}, {} {} in {:?} ({:.2}x){}",
+      name,
+      labels[0],
+      legacy,
+      legacy_time,
+      labels[1],
+      new,
+      new_time,
+      legacy_time.as_secs_f64() / new_time.as_secs_f64(),
+      if legacy == new { "" } else { " MISMATCH" }
+    );
+    Ok(())
+  });
+
+  fs::remove_file(file)?;
+  result
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+  let start = Instant::now();
+  let value = f();
+  (value, start.elapsed())
+}
@@ -1,9 +1,57 @@
-use std::fs;
-use std::io;
+use crate::bench;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+const NUMBERS_IN_TEXT: [&str; 9] = [
+  "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
 
 pub fn solve() {
-  println!("Part 1: {}", part1("inputs/day1/part1").unwrap());
-  println!("Part 2: {}", part2("inputs/day1/part1").unwrap());
+  println!(
+    "Part 1: {}",
+    calibrate_file("inputs/day1/part1", false).unwrap()
+  );
+  println!(
+    "Part 2: {}",
+    calibrate_file("inputs/day1/part1", true).unwrap()
+  );
+}
+
+pub fn run(args: &[String]) {
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("stream") => {
+      // `-` or no file reads from stdin so huge inputs can be piped in
+      let spelled = args.iter().any(|a| a == "--spelled");
+      let file = args[1..].iter().find(|a| !a.starts_with("--"));
+      let total = match file.map(|s| s.as_str()) {
+        None | Some("-") => calibrate(io::stdin().lock(), spelled),
+        Some(file) => calibrate_file(file, spelled),
+      };
+      total.map(|total| println!("Total: {}", total))
+    }
+    Some("bench") => match args.get(1).map(|s| s.parse::<usize>()) {
+      None => bench(1_000_000),
+      Some(Ok(lines)) => bench(lines),
+      Some(Err(_)) => Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("malformed line count `{}`", args[1]),
+      )),
+    },
+    Some(cmd) => {
+      println!("Unknown day1 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
 }
 
 fn part1(file: &str) -> io::Result<u32> {
@@ -35,9 +83,6 @@ fn part1(file: &str) -> io::Result<u32> {
 
 fn part2(file: &str) -> io::Result<u32> {
   let input = fs::read_to_string(file)?;
-  const NUMBERS_IN_TEXT: [&str; 9] = [
-    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
-  ];
   let mut total: u32 = 0;
 
   for line in input.lines() {
@@ -77,3 +122,223 @@ fn part2(file: &str) -> io::Result<u32> {
 
   Ok(total)
 }
+
+fn calibrate_file(file: &str, spelled: bool) -> io::Result<u64> {
+  calibrate(
+    BufReader::with_capacity(1 << 16, File::open(file)?),
+    spelled,
+  )
+}
+
+// walks the reader's own buffer byte by byte without splitting lines, so
+// memory use stays constant no matter how large the input or its lines are
+fn calibrate<R: BufRead>(mut reader: R, spelled: bool) -> io::Result<u64> {
+  let mut scanner = Scanner::default();
+
+  loop {
+    let chunk = reader.fill_buf()?;
+    if chunk.is_empty() {
+      break;
+    }
+    if spelled {
+      scanner.feed::<true>(chunk);
+    } else {
+      scanner.feed::<false>(chunk);
+    }
+    let len = chunk.len();
+    reader.consume(len);
+  }
+
+  Ok(scanner.finish())
+}
+
+// single forward pass: the first match of a line is kept, the last one is
+// overwritten on every hit. spelled digits are recognised when their last
+// letter arrives, using a rolling window of the previous bytes, so words
+// split across two chunks and overlaps like `oneight` need no look-ahead
+#[derive(Clone, Copy)]
+struct Scanner {
+  first: u8,
+  last: u8,
+  window: u64,
+  total: u64,
+}
+
+const NO_DIGIT: u8 = u8::MAX;
+
+impl Default for Scanner {
+  fn default() -> Scanner {
+    Scanner {
+      first: NO_DIGIT,
+      last: NO_DIGIT,
+      window: 0,
+      total: 0,
+    }
+  }
+}
+
+impl Scanner {
+  fn feed<const SPELLED: bool>(&mut self, chunk: &[u8]) {
+    // work on a copy so the hot loop keeps its state in registers, and keep
+    // the per-byte updates branch free since digits are scattered at random
+    let mut state = *self;
+
+    for &b in chunk {
+      if b == b'\n' {
+        state.end_line();
+        continue;
+      }
+
+      let mut digit = b.wrapping_sub(b'0');
+      if SPELLED {
+        state.window = (state.window << 8) | b as u64;
+        let spelled = spelled_digit_ending_in(state.window);
+        digit = if digit < 10 { digit } else { spelled };
+      }
+
+      let found = digit < 10;
+      state.last = if found { digit } else { state.last };
+      state.first = if found && state.first == NO_DIGIT {
+        digit
+      } else {
+        state.first
+      };
+    }
+
+    *self = state;
+  }
+
+  fn end_line(&mut self) {
+    if self.first != NO_DIGIT {
+      self.total += (self.first * 10 + self.last) as u64;
+    }
+    *self = Scanner {
+      total: self.total,
+      ..Scanner::default()
+    };
+  }
+
+  fn finish(mut self) -> u64 {
+    // the last line may not be terminated by a newline
+    self.end_line();
+    self.total
+  }
+}
+
+const fn pack(text: &[u8]) -> u64 {
+  let mut packed: u64 = 0;
+  let mut idx = 0;
+  while idx < text.len() {
+    packed = (packed << 8) | text[idx] as u64;
+    idx += 1;
+  }
+  packed
+}
+
+// multiplier picked by search so that the last three letters of the nine
+// words land in distinct slots of a 16 entry table
+const SUFFIX_HASH: u64 = 0xa11d_459a_2f97_8d87;
+
+const fn suffix_slot(window: u64) -> usize {
+  ((window & 0xff_ffff).wrapping_mul(SUFFIX_HASH) >> 60) as usize
+}
+
+// each slot holds (packed word, mask over its length, digit); empty slots
+// can never match because their mask clears every bit of the window
+const SPELLED_TABLE: [(u64, u64, u8); 16] = {
+  let mut table = [(u64::MAX, 0, NO_DIGIT); 16];
+  let mut idx = 0;
+  while idx < NUMBERS_IN_TEXT.len() {
+    let text = NUMBERS_IN_TEXT[idx].as_bytes();
+    let word = pack(text);
+    let slot = suffix_slot(word);
+    assert!(table[slot].2 == NO_DIGIT, "spelled digit suffixes collide");
+    table[slot] = (word, u64::MAX >> (64 - 8 * text.len()), idx as u8 + 1);
+    idx += 1;
+  }
+  table
+};
+
+fn spelled_digit_ending_in(window: u64) -> u8 {
+  let (word, mask, digit) = SPELLED_TABLE[suffix_slot(window)];
+  if window & mask == word {
+    digit
+  } else {
+    NO_DIGIT
+  }
+}
+
+fn bench(lines: usize) -> io::Result<()> {
+  bench::compare(
+    "day1",
+    lines,
+    "lines",
+    generate_input,
+    ["legacy", "streaming"],
+    &[
+      (
+        "Part 1",
+        |file| part1(file).map(u64::from),
+        |file| calibrate_file(file, false),
+      ),
+      (
+        "Part 2",
+        |file| part2(file).map(u64::from),
+        |file| calibrate_file(file, true),
+      ),
+    ],
+  )
+}
+
+fn generate_input(file: &str, lines: usize) -> io::Result<()> {
+  // mimics the puzzle input: mostly letters with a few digits and words mixed in
+  let mut rng = StdRng::seed_from_u64(2023);
+  let mut writer = BufWriter::new(File::create(file)?);
+
+  for _ in 0..lines {
+    let len = rng.gen_range(2..48);
+    let mut line: Vec<u8> = Vec::with_capacity(len + 1);
+    while line.len() < len {
+      if rng.gen_ratio(1, 8) {
+        line.extend(NUMBERS_IN_TEXT[rng.gen_range(0..9)].as_bytes());
+      } else if rng.gen_ratio(1, 8) {
+        line.push(rng.gen_range(b'1'..=b'9'));
+      } else {
+        line.push(rng.gen_range(b'a'..=b'z'));
+      }
+    }
+    // every line needs at least one digit for both scans to agree
+    line.push(rng.gen_range(b'1'..=b'9'));
+    line.push(b'\n');
+    writer.write_all(&line)?;
+  }
+
+  writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn test_calibrate_matches_legacy_across_chunks() {
+    // tiny buffers split the words across chunks, the last line has no newline
+    let input = "two1nine\neightwothree\nabcone2threexyz\nxtwone3four\n4nineeightseven2\n\
+                 zoneight234\n7pqrstsixteen\n1abc2\ntreb7uchet\nsevenine\noneight";
+    let path = env::temp_dir().join("aoc2023-day1-test-chunks");
+    let file = path.to_str().unwrap();
+    fs::write(file, input).unwrap();
+    let (legacy1, legacy2) = (part1(file).unwrap() as u64, part2(file).unwrap() as u64);
+    fs::remove_file(file).unwrap();
+
+    for capacity in 1..=8 {
+      let reader = || BufReader::with_capacity(capacity, input.as_bytes());
+      assert_eq!(calibrate(reader(), false).unwrap(), legacy1, "{}", capacity);
+      assert_eq!(calibrate(reader(), true).unwrap(), legacy2, "{}", capacity);
+    }
+
+    let oneight = BufReader::with_capacity(3, "oneight".as_bytes());
+    assert_eq!(calibrate(oneight, true).unwrap(), 18);
+  }
+}
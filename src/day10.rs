@@ -1,221 +1,728 @@
-use std::collections::HashSet;
+use std::fmt;
 use std::fs;
 use std::io;
 
-pub fn solve() {
-  println!("Part 1: {}", part1("inputs/day10/part1").unwrap());
-  println!("Part 2: {}", part2("inputs/day10/part1").unwrap());
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+  North,
+  East,
+  South,
+  West,
 }
 
-fn part1(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
-  let nrow = input.lines().count();
-  let ncol = input.lines().next().unwrap().len();
-  let mut map = input.chars().filter(|c| *c != '\n').collect::<Vec<char>>();
-  let (row, col) = get_starting_position(&map, nrow, ncol);
-  map[row * ncol + col] = '-';
+const DIRECTIONS: [Direction; 4] = [
+  Direction::North,
+  Direction::East,
+  Direction::South,
+  Direction::West,
+];
+
+const PIPES: [char; 6] = ['|', '-', 'L', 'J', '7', 'F'];
 
-  Ok((count_steps(&map, nrow, ncol, row, col) as f64 / 2.0).ceil() as u64)
+impl Direction {
+  fn opposite(self) -> Direction {
+    match self {
+      Direction::North => Direction::South,
+      Direction::East => Direction::West,
+      Direction::South => Direction::North,
+      Direction::West => Direction::East,
+    }
+  }
+
+  fn name(self) -> &'static str {
+    match self {
+      Direction::North => "north",
+      Direction::East => "east",
+      Direction::South => "south",
+      Direction::West => "west",
+    }
+  }
 }
 
-fn part2(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
-  let nrow = input.lines().count();
-  let ncol = input.lines().next().unwrap().len();
-  let mut map = input.chars().filter(|c| *c != '\n').collect::<Vec<char>>();
-  let (row, col) = get_starting_position(&map, nrow, ncol);
-  map[row * ncol + col] = '-';
+// the sides a tile opens to, empty for anything that is not a pipe
+fn connections(tile: char) -> &'static [Direction] {
+  match tile {
+    '|' => &[Direction::North, Direction::South],
+    '-' => &[Direction::East, Direction::West],
+    'L' => &[Direction::North, Direction::East],
+    'J' => &[Direction::North, Direction::West],
+    '7' => &[Direction::South, Direction::West],
+    'F' => &[Direction::South, Direction::East],
+    _ => &[],
+  }
+}
 
-  Ok(count_enclosed_tiles(&map, nrow, ncol, row, col))
+fn pipe_between(a: Direction, b: Direction) -> char {
+  *PIPES
+    .iter()
+    .find(|&&pipe| connections(pipe).contains(&a) && connections(pipe).contains(&b))
+    .unwrap()
+}
+
+struct Grid {
+  tiles: Vec<char>,
+  nrow: usize,
+  ncol: usize,
 }
 
-fn count_steps(map: &Vec<char>, nrow: usize, ncol: usize, row: usize, col: usize) -> u64 {
-  let mut visited: HashSet<usize> = HashSet::new();
-  let mut count: u64 = 0;
-  visited.insert(row * ncol + col);
+impl Grid {
+  fn load(file: &str) -> io::Result<Grid> {
+    Grid::parse(&fs::read_to_string(file)?)
+  }
 
-  let mut current_row = row;
-  let mut current_col = col;
+  fn parse(input: &str) -> io::Result<Grid> {
+    let mut tiles: Vec<char> = Vec::new();
+    let mut nrow: usize = 0;
+    let mut ncol: usize = 0;
 
-  loop {
-    let next_position = get_next_position(&map, nrow, ncol, current_row, current_col, &visited);
-    if next_position.is_none() {
-      break;
+    for (idx, line) in input.lines().enumerate() {
+      let len = line.chars().count();
+      if idx == 0 {
+        ncol = len;
+      } else if len != ncol {
+        return Err(invalid_data(format!(
+          "line {} has {} tiles, expected {}",
+          idx + 1,
+          len,
+          ncol
+        )));
+      }
+      tiles.extend(line.chars());
+      nrow += 1;
     }
-    current_row = next_position.unwrap().0;
-    current_col = next_position.unwrap().1;
-    visited.insert(current_row * ncol + current_col);
-    count += 1;
+
+    Ok(Grid { tiles, nrow, ncol })
   }
 
-  count
+  fn get(&self, (row, col): (usize, usize)) -> char {
+    self.tiles[row * self.ncol + col]
+  }
+
+  fn step(&self, (row, col): (usize, usize), direction: Direction) -> Option<(usize, usize)> {
+    match direction {
+      Direction::North if row > 0 => Some((row - 1, col)),
+      Direction::East if col + 1 < self.ncol => Some((row, col + 1)),
+      Direction::South if row + 1 < self.nrow => Some((row + 1, col)),
+      Direction::West if col > 0 => Some((row, col - 1)),
+      _ => None,
+    }
+  }
+
+  fn start(&self) -> io::Result<(usize, usize)> {
+    let idx = self
+      .tiles
+      .iter()
+      .position(|&tile| tile == 'S')
+      .ok_or_else(|| invalid_data("no S tile".to_string()))?;
+    Ok((idx / self.ncol, idx % self.ncol))
+  }
 }
 
-fn get_next_position(
-  map: &Vec<char>,
-  nrow: usize,
-  ncol: usize,
-  row: usize,
-  col: usize,
-  visited: &HashSet<usize>,
-) -> Option<(usize, usize)> {
-  let idx = row * ncol + col;
-  let can_go_up = row > 0 && !visited.contains(&((row - 1) * ncol + col));
-  let can_go_down = row < nrow - 1 && !visited.contains(&((row + 1) * ncol + col));
-  let can_go_left = col > 0 && !visited.contains(&(row * ncol + col - 1));
-  let can_go_right = col < ncol - 1 && !visited.contains(&(row * ncol + col + 1));
-
-  match map[idx] {
-    '|' => {
-      if can_go_up {
-        Some((row - 1, col))
-      } else if can_go_down {
-        Some((row + 1, col))
-      } else {
-        None
-      }
+// the loop through S, `tiles` in walking order starting at S
+struct PipeLoop {
+  start: (usize, usize),
+  start_shape: char,
+  tiles: Vec<(usize, usize)>,
+}
+
+impl PipeLoop {
+  // S is whichever pipe joins two neighbours that lead around back to it
+  fn find(grid: &Grid) -> io::Result<PipeLoop> {
+    let start = grid.start()?;
+    let open = start_sides(grid, start);
+    if open.is_empty() {
+      return Err(invalid_data(format!(
+        "no pipe connects to S at ({}, {})",
+        start.0, start.1
+      )));
     }
-    '-' => {
-      if can_go_left {
-        Some((row, col - 1))
-      } else if can_go_right {
-        Some((row, col + 1))
-      } else {
-        None
+
+    let mut breaks: Vec<String> = Vec::new();
+    for &out in &open {
+      match walk(grid, start, out) {
+        Ok((tiles, back)) if back != out => {
+          return Ok(PipeLoop {
+            start,
+            start_shape: pipe_between(out, back),
+            tiles,
+          })
+        }
+        Ok(_) => {}
+        Err(broken) => breaks.push(format!("heading {}, {}", out.name(), broken)),
       }
     }
-    'F' => {
-      if can_go_down {
-        Some((row + 1, col))
-      } else if can_go_right {
-        Some((row, col + 1))
-      } else {
-        None
-      }
+
+    Err(invalid_data(format!(
+      "the loop through S at ({}, {}) does not close: {}",
+      start.0,
+      start.1,
+      breaks.join("; ")
+    )))
+  }
+
+  fn shape(&self, grid: &Grid, pos: (usize, usize)) -> char {
+    if pos == self.start {
+      self.start_shape
+    } else {
+      grid.get(pos)
     }
-    'L' => {
-      if can_go_up {
-        Some((row - 1, col))
-      } else if can_go_right {
-        Some((row, col + 1))
-      } else {
-        None
+  }
+
+  // the corners of the loop, the only tiles where its direction turns
+  fn vertices(&self, grid: &Grid) -> Vec<(usize, usize)> {
+    self
+      .tiles
+      .iter()
+      .copied()
+      .filter(|&pos| !matches!(self.shape(grid, pos), '|' | '-'))
+      .collect()
+  }
+
+  fn farthest_distance(&self) -> u64 {
+    self.tiles.len() as u64 / 2
+  }
+
+  // interior lattice points from Pick's theorem A = I + B / 2 - 1, with the
+  // area A from the shoelace formula over the vertices
+  fn area_by_pick(&self, grid: &Grid) -> u64 {
+    let vertices = self.vertices(grid);
+    let twice_area: i64 = vertices
+      .iter()
+      .zip(vertices.iter().cycle().skip(1))
+      .map(|(&(r1, c1), &(r2, c2))| r1 as i64 * c2 as i64 - r2 as i64 * c1 as i64)
+      .sum::<i64>()
+      .abs();
+    ((twice_area - self.tiles.len() as i64 + 2) / 2) as u64
+  }
+
+  // scanning each row, the inside flips whenever a loop tile opens to the
+  // north, so a `F--J` run crosses the loop once and a `F--7` run not at all
+  fn count_enclosed_tiles(&self, grid: &Grid) -> u64 {
+    let mut on_loop = vec![false; grid.tiles.len()];
+    for &(row, col) in &self.tiles {
+      on_loop[row * grid.ncol + col] = true;
+    }
+
+    let mut total: u64 = 0;
+    for row in 0..grid.nrow {
+      let mut is_inside = false;
+      for col in 0..grid.ncol {
+        if on_loop[row * grid.ncol + col] {
+          if connections(self.shape(grid, (row, col))).contains(&Direction::North) {
+            is_inside = !is_inside;
+          }
+        } else if is_inside {
+          total += 1;
+        }
       }
     }
-    'J' => {
-      if can_go_up {
-        Some((row - 1, col))
-      } else if can_go_left {
-        Some((row, col - 1))
-      } else {
-        None
+
+    total
+  }
+
+  fn enclosed_tiles(&self, grid: &Grid) -> io::Result<u64> {
+    let by_pick = self.area_by_pick(grid);
+    let by_scanline = self.count_enclosed_tiles(grid);
+    if by_pick != by_scanline {
+      return Err(invalid_data(format!(
+        "shoelace and Pick give {} enclosed tiles but the scanline gives {}",
+        by_pick, by_scanline
+      )));
+    }
+    Ok(by_pick)
+  }
+
+  // every tile becomes a 3x3 block with its pipe drawn through the middle,
+  // so two pipes that only touch leave a free lane to squeeze through
+  fn upscale(&self, grid: &Grid) -> Upscaled {
+    let nrow = grid.nrow * 3;
+    let ncol = grid.ncol * 3;
+    let mut walls = vec![false; nrow * ncol];
+    for &pos in &self.tiles {
+      let (row, col) = (pos.0 * 3 + 1, pos.1 * 3 + 1);
+      walls[row * ncol + col] = true;
+      for &side in connections(self.shape(grid, pos)) {
+        let cell = match side {
+          Direction::North => (row - 1) * ncol + col,
+          Direction::East => row * ncol + col + 1,
+          Direction::South => (row + 1) * ncol + col,
+          Direction::West => row * ncol + col - 1,
+        };
+        walls[cell] = true;
       }
     }
-    '7' => {
-      if can_go_down {
-        Some((row + 1, col))
-      } else if can_go_left {
-        Some((row, col - 1))
-      } else {
-        None
+
+    // the corner cells of a block are never walls, so the whole border is
+    // reachable from the top left one
+    let mut outside = vec![false; nrow * ncol];
+    let mut stack = vec![0];
+    outside[0] = true;
+    while let Some(cell) = stack.pop() {
+      let (row, col) = (cell / ncol, cell % ncol);
+      let mut neighbours: Vec<usize> = Vec::with_capacity(4);
+      if row > 0 {
+        neighbours.push(cell - ncol);
+      }
+      if row + 1 < nrow {
+        neighbours.push(cell + ncol);
+      }
+      if col > 0 {
+        neighbours.push(cell - 1);
+      }
+      if col + 1 < ncol {
+        neighbours.push(cell + 1);
+      }
+      for next in neighbours {
+        if !walls[next] && !outside[next] {
+          outside[next] = true;
+          stack.push(next);
+        }
       }
     }
-    _ => None,
+
+    Upscaled {
+      walls,
+      outside,
+      ncol,
+    }
   }
-}
 
-fn get_starting_position(map: &Vec<char>, nrow: usize, ncol: usize) -> (usize, usize) {
-  let mut row: usize = 0;
-  let mut col: usize = 0;
-  for idx in 0..map.len() {
-    if map[idx] == 'S' {
-      row = idx / ncol;
-      col = idx % ncol;
-      break;
+  // a tile off the loop is outside when the flood reached its centre cell
+  fn regions(&self, grid: &Grid) -> Vec<Region> {
+    let upscaled = self.upscale(grid);
+    let mut regions = vec![Region::Inside; grid.tiles.len()];
+    for &(row, col) in &self.tiles {
+      regions[row * grid.ncol + col] = Region::Loop;
     }
+    for (idx, region) in regions.iter_mut().enumerate() {
+      let centre = (idx / grid.ncol * 3 + 1) * upscaled.ncol + idx % grid.ncol * 3 + 1;
+      if *region == Region::Inside && upscaled.outside[centre] {
+        *region = Region::Outside;
+      }
+    }
+    regions
   }
 
-  (row, col)
+  fn count_enclosed_by_flood_fill(&self, grid: &Grid) -> u64 {
+    self
+      .regions(grid)
+      .iter()
+      .filter(|&&region| region == Region::Inside)
+      .count() as u64
+  }
 }
 
-fn get_pipes_of_loop(
-  map: &Vec<char>,
-  nrow: usize,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+  Loop,
+  Inside,
+  Outside,
+}
+
+struct Upscaled {
+  walls: Vec<bool>,
+  outside: Vec<bool>,
   ncol: usize,
-  start_row: usize,
-  start_col: usize,
-) -> HashSet<usize> {
-  let mut visited: HashSet<usize> = HashSet::new();
-  visited.insert(start_row * ncol + start_col);
+}
+
+// where a walk along the pipes breaks off: the tile at `pos` opens to
+// `side`, but the neighbour there does not open back, or there is none
+struct Break {
+  pos: (usize, usize),
+  tile: char,
+  side: Direction,
+  neighbour: Option<((usize, usize), char)>,
+}
+
+impl fmt::Display for Break {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "`{}` at ({}, {}) opens {} ",
+      self.tile,
+      self.pos.0,
+      self.pos.1,
+      self.side.name()
+    )?;
+    match self.neighbour {
+      Some(((row, col), tile)) => write!(f, "into `{}` at ({}, {})", tile, row, col),
+      None => write!(f, "off the map"),
+    }
+  }
+}
 
-  let mut current_row = start_row;
-  let mut current_col = start_col;
+// the sides of S with a neighbouring pipe opening back towards it
+fn start_sides(grid: &Grid, start: (usize, usize)) -> Vec<Direction> {
+  DIRECTIONS
+    .iter()
+    .copied()
+    .filter(|&dir| {
+      grid
+        .step(start, dir)
+        .is_some_and(|pos| connections(grid.get(pos)).contains(&dir.opposite()))
+    })
+    .collect()
+}
+
+// walks out of `start` to the `out` side until the walk comes back into it,
+// returning the tiles on the way and the side it came back through
+fn walk(
+  grid: &Grid,
+  start: (usize, usize),
+  out: Direction,
+) -> Result<(Vec<(usize, usize)>, Direction), Break> {
+  let mut tiles = vec![start];
+  let mut pos = start;
+  let mut heading = out;
 
   loop {
-    let next_position = get_next_position(&map, nrow, ncol, current_row, current_col, &visited);
-    if next_position.is_none() {
-      break;
+    let next = grid.step(pos, heading);
+    let broken = |neighbour| Break {
+      pos,
+      tile: grid.get(pos),
+      side: heading,
+      neighbour,
+    };
+    let next = next.ok_or_else(|| broken(None))?;
+    if next == start {
+      return Ok((tiles, heading.opposite()));
+    }
+    let from = heading.opposite();
+    let sides = connections(grid.get(next));
+    if !sides.contains(&from) {
+      return Err(broken(Some((next, grid.get(next)))));
     }
-    current_row = next_position.unwrap().0;
-    current_col = next_position.unwrap().1;
-    visited.insert(current_row * ncol + current_col);
+    heading = *sides.iter().find(|&&side| side != from).unwrap();
+    pos = next;
+    tiles.push(pos);
   }
+}
 
-  visited
+// a connected group of pipes, closed when every pipe in it links up on
+// both of its sides
+struct Component {
+  tiles: Vec<(usize, usize)>,
+  contains_start: bool,
+  breaks: Vec<Break>,
+  closed: bool,
 }
 
-fn count_enclosed_tiles(
-  map: &Vec<char>,
-  nrow: usize,
-  ncol: usize,
-  start_row: usize,
-  start_col: usize,
-) -> u64 {
-  let pipes_of_loop = get_pipes_of_loop(map, nrow, ncol, start_row, start_col);
-  let mut is_inside = false;
-  let mut total: u64 = 0;
-  let mut entering_pipe: Option<char> = None;
-
-  for r in 0..nrow {
-    is_inside = false;
-    entering_pipe = None;
-    for c in 0..ncol {
-      let idx = r * ncol + c;
-      if pipes_of_loop.contains(&idx) {
-        match map[idx] {
-          '|' => {
-            if is_inside {
-              is_inside = false;
-              entering_pipe = None;
-            } else {
-              is_inside = true;
-              entering_pipe = Some('|');
-            }
-          }
-          'F' | 'L' => {
-            entering_pipe = Some(map[idx]);
-          }
-          '7' => {
-            if let Some(pipe) = entering_pipe {
-              if pipe == 'L' {
-                is_inside = !is_inside;
-              }
+// splits every pipe of the map into closed loops and dangling paths, S takes
+// its inferred shape when it closes a loop and otherwise opens to every
+// neighbour that opens towards it
+fn analyze_network(grid: &Grid) -> Vec<Component> {
+  let start = grid.start().ok();
+  let start_open: Vec<Direction> = match start {
+    Some(start) => match PipeLoop::find(grid) {
+      Ok(pipe_loop) => connections(pipe_loop.start_shape).to_vec(),
+      Err(_) => start_sides(grid, start),
+    },
+    None => Vec::new(),
+  };
+  let sides = |pos: (usize, usize)| -> &[Direction] {
+    if Some(pos) == start {
+      &start_open
+    } else {
+      connections(grid.get(pos))
+    }
+  };
+
+  let mut seen = vec![false; grid.tiles.len()];
+  let mut components: Vec<Component> = Vec::new();
+  for idx in 0..grid.tiles.len() {
+    let first = (idx / grid.ncol, idx % grid.ncol);
+    if seen[idx] || sides(first).is_empty() {
+      continue;
+    }
+
+    seen[idx] = true;
+    let mut tiles: Vec<(usize, usize)> = Vec::new();
+    let mut breaks: Vec<Break> = Vec::new();
+    let mut queue = vec![first];
+    while let Some(pos) = queue.pop() {
+      tiles.push(pos);
+      for &side in sides(pos) {
+        let next = grid.step(pos, side);
+        match next {
+          Some(next) if sides(next).contains(&side.opposite()) => {
+            let next_idx = next.0 * grid.ncol + next.1;
+            if !seen[next_idx] {
+              seen[next_idx] = true;
+              queue.push(next);
             }
           }
-          'J' => {
-            if let Some(pipe) = entering_pipe {
-              if pipe == 'F' {
-                is_inside = !is_inside;
-              }
-            }
+          _ => breaks.push(Break {
+            pos,
+            tile: grid.get(pos),
+            side,
+            neighbour: next.map(|next| (next, grid.get(next))),
+          }),
+        }
+      }
+    }
+
+    tiles.sort();
+    let contains_start = start.is_some_and(|start| tiles.contains(&start));
+    let closed = breaks.is_empty() && (!contains_start || start_open.len() == 2);
+    components.push(Component {
+      tiles,
+      contains_start,
+      breaks,
+      closed,
+    });
+  }
+
+  components
+}
+
+pub fn solve() {
+  println!("Part 1: {}", part1("inputs/day10/part1").unwrap());
+  println!("Part 2: {}", part2("inputs/day10/part1").unwrap());
+}
+
+pub fn run(args: &[String]) {
+  let file = |idx: usize| args.get(idx).map_or("inputs/day10/part1", |s| s.as_str());
+  // an optional flag before the file
+  let flag_and_file = |flag: &str| {
+    let set = args.get(1).is_some_and(|arg| arg == flag);
+    (set, file(if set { 2 } else { 1 }))
+  };
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("loop") => print_loop(file(1)),
+    Some("flood") => print_flood_fill(file(1)),
+    // day10 render [--upscaled] [file]
+    Some("render") => {
+      let (upscaled, file) = flag_and_file("--upscaled");
+      render(file, upscaled).map(|output| print!("{}", output))
+    }
+    // day10 network [--all] [file]
+    Some("network") => {
+      let (all, file) = flag_and_file("--all");
+      print_network(file, all)
+    }
+    Some(cmd) => {
+      println!("Unknown day10 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
+fn part1(file: &str) -> io::Result<u64> {
+  let grid = Grid::load(file)?;
+  Ok(PipeLoop::find(&grid)?.farthest_distance())
+}
+
+fn part2(file: &str) -> io::Result<u64> {
+  let grid = Grid::load(file)?;
+  PipeLoop::find(&grid)?.enclosed_tiles(&grid)
+}
+
+fn print_loop(file: &str) -> io::Result<()> {
+  let grid = Grid::load(file)?;
+  let pipe_loop = PipeLoop::find(&grid)?;
+
+  println!(
+    "S at ({}, {}) is a `{}`",
+    pipe_loop.start.0, pipe_loop.start.1, pipe_loop.start_shape
+  );
+  println!(
+    "Loop: {} tiles, {} vertices, farthest tile {} steps away",
+    pipe_loop.tiles.len(),
+    pipe_loop.vertices(&grid).len(),
+    pipe_loop.farthest_distance()
+  );
+  println!(
+    "Enclosed tiles: {} by shoelace and Pick, {} by scanline",
+    pipe_loop.area_by_pick(&grid),
+    pipe_loop.count_enclosed_tiles(&grid)
+  );
+  Ok(())
+}
+
+fn print_flood_fill(file: &str) -> io::Result<()> {
+  let grid = Grid::load(file)?;
+  let pipe_loop = PipeLoop::find(&grid)?;
+  let by_flood_fill = pipe_loop.count_enclosed_by_flood_fill(&grid);
+  let by_scanline = pipe_loop.count_enclosed_tiles(&grid);
+
+  println!(
+    "Enclosed tiles: {} by 3x flood fill, {} by scanline",
+    by_flood_fill, by_scanline
+  );
+  if by_flood_fill != by_scanline {
+    return Err(invalid_data("flood fill and scanline disagree".to_string()));
+  }
+  Ok(())
+}
+
+// loop pipes are drawn with box-drawing characters and S in bold, tiles
+// enclosed by the loop are green and the ones outside it dimmed blue, the
+// upscaled view shows the lanes the flood fill squeezed through
+fn render(file: &str, upscaled: bool) -> io::Result<String> {
+  const GREEN: &str = "\x1b[42;30m";
+  const DIM_BLUE: &str = "\x1b[2;34m";
+  const BOLD: &str = "\x1b[1;33m";
+  const RESET: &str = "\x1b[0m";
+
+  let grid = Grid::load(file)?;
+  let pipe_loop = PipeLoop::find(&grid)?;
+  let mut output = String::new();
+
+  if upscaled {
+    let cells = pipe_loop.upscale(&grid);
+    for (idx, &wall) in cells.walls.iter().enumerate() {
+      if wall {
+        output.push('█');
+      } else if cells.outside[idx] {
+        output.push_str(&format!("{}·{}", DIM_BLUE, RESET));
+      } else {
+        output.push_str(&format!("{} {}", GREEN, RESET));
+      }
+      if (idx + 1) % cells.ncol == 0 {
+        output.push('\n');
+      }
+    }
+    return Ok(output);
+  }
+
+  let regions = pipe_loop.regions(&grid);
+  for row in 0..grid.nrow {
+    for col in 0..grid.ncol {
+      let pos = (row, col);
+      match regions[row * grid.ncol + col] {
+        Region::Loop => {
+          let pipe = box_drawing(pipe_loop.shape(&grid, pos));
+          if pos == pipe_loop.start {
+            output.push_str(&format!("{}{}{}", BOLD, pipe, RESET));
+          } else {
+            output.push(pipe);
           }
-          _ => {}
         }
-      } else if is_inside {
-        total += 1;
+        Region::Inside => output.push_str(&format!("{}{}{}", GREEN, grid.get(pos), RESET)),
+        Region::Outside => output.push_str(&format!("{}{}{}", DIM_BLUE, grid.get(pos), RESET)),
       }
     }
+    output.push('\n');
+  }
+
+  Ok(output)
+}
+
+fn box_drawing(pipe: char) -> char {
+  match pipe {
+    '|' => '│',
+    '-' => '─',
+    'L' => '└',
+    'J' => '┘',
+    '7' => '┐',
+    'F' => '┌',
+    _ => pipe,
+  }
+}
+
+fn print_network(file: &str, all: bool) -> io::Result<()> {
+  let grid = Grid::load(file)?;
+  let components = analyze_network(&grid);
+  let loops = components.iter().filter(|c| c.closed).count();
+
+  println!(
+    "Closed loops: {}, dangling paths: {}",
+    loops,
+    components.len() - loops
+  );
+  for (idx, component) in components.iter().enumerate() {
+    if !all && !component.contains_start {
+      continue;
+    }
+    let (row, col) = component.tiles[0];
+    print!(
+      "{} {}: {} tiles from ({}, {})",
+      if component.closed { "loop" } else { "path" },
+      idx + 1,
+      component.tiles.len(),
+      row,
+      col
+    );
+    if component.contains_start {
+      print!(", contains S");
+    }
+    println!();
+    for broken in &component.breaks {
+      println!("  {}", broken);
+    }
+  }
+  if let Err(err) = PipeLoop::find(&grid) {
+    println!("{}", err);
+  }
+  Ok(())
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pick_matches_scanline() {
+    let cases = [
+      // S on a vertical crossing of the scanline
+      ("..........\n.F------7.\n.SF----7|.\n.||....||.\n.||....||.\n.|L-7F-J|.\n.|..||..|.\n.L--JL--J.\n..........", '|', 4),
+      // S a corner, with a dead end and junk pipes next to it
+      ("7-F7-\n.FJ|7\nSJLL7\n|F--J\nLJ.LJ", 'F', 1),
+      ("-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF", 'F', 1),
+      (".F----7F7F7F7F-7....\n.|F--7||||||||FJ....\n.||.FJ||||||||L7....\nFJL7L7LJLJ||LJ.L-7..\nL--J.L7...LJS7F-7L7.\n....F-J..F7FJ|L7L7L7\n....L7.F7||L7|.L7L7|\n.....|FJLJ|FJ|F7|.LJ\n....FJL-7.||.||||...\n....L---J.LJ.LJLJ...", 'F', 8),
+    ];
+    for (input, shape, enclosed) in cases {
+      let grid = Grid::parse(input).unwrap();
+      let pipe_loop = PipeLoop::find(&grid).unwrap();
+      assert_eq!(pipe_loop.start_shape, shape);
+      assert_eq!(pipe_loop.area_by_pick(&grid), enclosed);
+      assert_eq!(pipe_loop.count_enclosed_tiles(&grid), enclosed);
+      assert_eq!(pipe_loop.count_enclosed_by_flood_fill(&grid), enclosed);
+    }
   }
 
-  total
+  #[test]
+  fn test_network_loops_and_paths() {
+    let grid = Grid::parse("F7.F7\nSJ.LJ\n.F-7.\n.|.-.").unwrap();
+    let components = analyze_network(&grid);
+    let summary: Vec<(usize, bool, bool)> = components
+      .iter()
+      .map(|c| (c.tiles.len(), c.closed, c.contains_start))
+      .collect();
+    assert_eq!(
+      summary,
+      vec![
+        (4, true, true),
+        (4, true, false),
+        (4, false, false),
+        (1, false, false)
+      ]
+    );
+    assert_eq!(components[2].breaks.len(), 2);
+  }
+
+  #[test]
+  fn test_broken_loop_points_at_tile() {
+    let grid = Grid::parse("S-7\n|.|\nL-.").unwrap();
+    let err = PipeLoop::find(&grid).err().unwrap();
+    assert_eq!(
+      err.to_string(),
+      "the loop through S at (0, 0) does not close: \
+       heading east, `|` at (1, 2) opens south into `.` at (2, 2); \
+       heading south, `-` at (2, 1) opens east into `.` at (2, 2)"
+    );
+    assert!(!analyze_network(&grid)[0].closed);
+  }
 }
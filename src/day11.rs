@@ -1,64 +1,366 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 
+const DEFAULT_FILE: &str = "inputs/day11/part1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+  Manhattan,
+  Chebyshev,
+}
+
+impl Metric {
+  fn parse(name: &str) -> io::Result<Metric> {
+    match name {
+      "manhattan" => Ok(Metric::Manhattan),
+      "chebyshev" => Ok(Metric::Chebyshev),
+      _ => Err(invalid_input(format!("unknown metric `{}`", name))),
+    }
+  }
+
+  fn distance(&self, (row1, col1): (u64, u64), (row2, col2): (u64, u64)) -> u64 {
+    let rows = row1.abs_diff(row2);
+    let cols = col1.abs_diff(col2);
+    match self {
+      Metric::Manhattan => rows + cols,
+      Metric::Chebyshev => rows.max(cols),
+    }
+  }
+}
+
+// the galaxies at their expanded (row, col) coordinates, numbered from 0 in
+// reading order
+struct GalaxyMap {
+  galaxies: Vec<(u64, u64)>,
+  metric: Metric,
+}
+
+impl GalaxyMap {
+  fn load(
+    file: &str,
+    row_expansion: u64,
+    col_expansion: u64,
+    metric: Metric,
+  ) -> io::Result<GalaxyMap> {
+    let (map, nrow, ncol) = read_map(file)?;
+    Ok(GalaxyMap::build(
+      &map,
+      nrow,
+      ncol,
+      row_expansion,
+      col_expansion,
+      metric,
+    ))
+  }
+
+  fn build(
+    map: &[char],
+    nrow: usize,
+    ncol: usize,
+    row_expansion: u64,
+    col_expansion: u64,
+    metric: Metric,
+  ) -> GalaxyMap {
+    let (empty_rows, empty_cols) = get_empty_rows_and_cols(map, nrow, ncol);
+    let row_positions = expanded_positions(nrow, &empty_rows, row_expansion);
+    let col_positions = expanded_positions(ncol, &empty_cols, col_expansion);
+
+    let galaxies = map
+      .iter()
+      .enumerate()
+      .filter(|(_, &value)| value == '#')
+      .map(|(idx, _)| (row_positions[idx / ncol], col_positions[idx % ncol]))
+      .collect();
+
+    GalaxyMap { galaxies, metric }
+  }
+
+  fn distance(&self, a: usize, b: usize) -> u64 {
+    self.metric.distance(self.galaxies[a], self.galaxies[b])
+  }
+
+  // a Manhattan distance is the sum of the row and column gaps, so each axis
+  // is summed on its own; a Chebyshev distance is half the Manhattan distance
+  // once the map is rotated by 45 degrees to (row + col, row - col)
+  fn sum_of_distances(&self) -> u64 {
+    match self.metric {
+      Metric::Manhattan => {
+        let rows = self.galaxies.iter().map(|&(row, _)| row).collect();
+        let cols = self.galaxies.iter().map(|&(_, col)| col).collect();
+        sum_of_pairwise_gaps(rows) + sum_of_pairwise_gaps(cols)
+      }
+      Metric::Chebyshev => {
+        // shifted by the largest column to keep row - col unsigned
+        let max_col = self.galaxies.iter().map(|&(_, col)| col).max().unwrap_or(0);
+        let sums = self.galaxies.iter().map(|&(row, col)| row + col).collect();
+        let diffs = self
+          .galaxies
+          .iter()
+          .map(|&(row, col)| row + max_col - col)
+          .collect();
+        (sum_of_pairwise_gaps(sums) + sum_of_pairwise_gaps(diffs)) / 2
+      }
+    }
+  }
+
+  fn others(&self, galaxy: usize) -> impl Iterator<Item = (usize, u64)> + '_ {
+    (0..self.galaxies.len())
+      .filter(move |&other| other != galaxy)
+      .map(move |other| (other, self.distance(galaxy, other)))
+  }
+
+  // ties go to the galaxy that comes first in reading order
+  fn nearest(&self, galaxy: usize) -> Option<(usize, u64)> {
+    self
+      .others(galaxy)
+      .min_by_key(|&(other, distance)| (distance, other))
+  }
+
+  fn farthest(&self, galaxy: usize) -> Option<(usize, u64)> {
+    self
+      .others(galaxy)
+      .max_by_key(|&(other, distance)| (distance, usize::MAX - other))
+  }
+
+  // number of pairs per distance bucket, keyed by the bucket start
+  fn histogram(&self, bucket: u64) -> BTreeMap<u64, u64> {
+    let mut counts: BTreeMap<u64, u64> = BTreeMap::new();
+    for a in 0..self.galaxies.len() {
+      for b in a + 1..self.galaxies.len() {
+        let distance = self.distance(a, b);
+        *counts.entry(distance / bucket * bucket).or_insert(0) += 1;
+      }
+    }
+    counts
+  }
+
+  fn id(&self, text: &str) -> io::Result<usize> {
+    text
+      .parse::<usize>()
+      .ok()
+      .filter(|&id| (1..=self.galaxies.len()).contains(&id))
+      .map(|id| id - 1)
+      .ok_or_else(|| {
+        invalid_input(format!(
+          "no galaxy `{}`, galaxies are numbered 1 to {}",
+          text,
+          self.galaxies.len()
+        ))
+      })
+  }
+}
+
 pub fn solve() {
-  println!("Part 1: {}", part1("inputs/day11/part1").unwrap());
-  println!("Part 2: {}", part2("inputs/day11/part1").unwrap());
+  println!("Part 1: {}", part1(DEFAULT_FILE).unwrap());
+  println!("Part 2: {}", part2(DEFAULT_FILE).unwrap());
+}
+
+// day11 [--expansion N] [--rows N] [--cols N] [--metric manhattan|chebyshev] <command>
+//   sum [file]
+//   expand <factor> [file], sum with both axes expanded by factor
+//   distance <galaxy> <galaxy> [file]
+//   neighbours [file]
+//   histogram [bucket] [file]
+pub fn run(args: &[String]) {
+  let result = parse_options(args).and_then(|(options, positional)| {
+    let load = |file: Option<&&str>| {
+      GalaxyMap::load(
+        file.unwrap_or(&DEFAULT_FILE),
+        options.row_expansion,
+        options.col_expansion,
+        options.metric,
+      )
+    };
+    match positional.as_slice() {
+      [] => {
+        solve();
+        Ok(())
+      }
+      ["sum", rest @ ..] => {
+        println!(
+          "Sum of distances: {}",
+          load(rest.first())?.sum_of_distances()
+        );
+        Ok(())
+      }
+      ["expand", factor, rest @ ..] => {
+        let factor = factor
+          .parse::<u64>()
+          .map_err(|_| invalid_input(format!("malformed number `{}`", factor)))?;
+        let galaxies = GalaxyMap::load(
+          rest.first().unwrap_or(&DEFAULT_FILE),
+          factor,
+          factor,
+          options.metric,
+        )?;
+        println!("Sum of distances: {}", galaxies.sum_of_distances());
+        Ok(())
+      }
+      ["expand"] => Err(invalid_input(String::from("missing expansion factor"))),
+      ["distance", a, b, rest @ ..] => {
+        let galaxies = load(rest.first())?;
+        let (a, b) = (galaxies.id(a)?, galaxies.id(b)?);
+        println!(
+          "Distance between galaxies {} and {}: {}",
+          a + 1,
+          b + 1,
+          galaxies.distance(a, b)
+        );
+        Ok(())
+      }
+      ["neighbours", rest @ ..] => print_neighbours(&load(rest.first())?),
+      ["histogram", rest @ ..] => {
+        let bucket = match rest.first() {
+          Some(bucket) => Some(
+            bucket
+              .parse::<u64>()
+              .ok()
+              .filter(|&bucket| bucket > 0)
+              .ok_or_else(|| invalid_input(format!("malformed bucket size `{}`", bucket)))?,
+          ),
+          None => None,
+        };
+        print_histogram(&load(rest.get(1))?, bucket)
+      }
+      [cmd, ..] => Err(invalid_input(format!("unknown day11 command `{}`", cmd))),
+    }
+  });
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
+struct Options {
+  row_expansion: u64,
+  col_expansion: u64,
+  metric: Metric,
+}
+
+fn parse_options(args: &[String]) -> io::Result<(Options, Vec<&str>)> {
+  let mut options = Options {
+    row_expansion: 2,
+    col_expansion: 2,
+    metric: Metric::Manhattan,
+  };
+  let mut positional: Vec<&str> = Vec::new();
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    let mut value = || {
+      iter
+        .next()
+        .ok_or_else(|| invalid_input(format!("missing value for {}", arg)))
+    };
+    let number = |text: &String| {
+      text
+        .parse::<u64>()
+        .map_err(|_| invalid_input(format!("malformed number `{}`", text)))
+    };
+    match arg.as_str() {
+      "--expansion" => {
+        let expansion = number(value()?)?;
+        options.row_expansion = expansion;
+        options.col_expansion = expansion;
+      }
+      "--rows" => options.row_expansion = number(value()?)?,
+      "--cols" => options.col_expansion = number(value()?)?,
+      "--metric" => options.metric = Metric::parse(value()?)?,
+      _ => positional.push(arg),
+    }
+  }
+
+  Ok((options, positional))
 }
 
 fn part1(file: &str) -> io::Result<u64> {
-  solveImpl(file, 2)
+  Ok(GalaxyMap::load(file, 2, 2, Metric::Manhattan)?.sum_of_distances())
 }
 
 fn part2(file: &str) -> io::Result<u64> {
-  solveImpl(file, 1000000)
+  Ok(GalaxyMap::load(file, 1000000, 1000000, Metric::Manhattan)?.sum_of_distances())
 }
 
-fn solveImpl(file: &str, expansion_times: u64) -> io::Result<u64> {
-  let (map, nrow, ncol) = read_map(file)?;
+fn print_neighbours(galaxies: &GalaxyMap) -> io::Result<()> {
+  for galaxy in 0..galaxies.galaxies.len() {
+    let (row, col) = galaxies.galaxies[galaxy];
+    match (galaxies.nearest(galaxy), galaxies.farthest(galaxy)) {
+      (Some((nearest, near)), Some((farthest, far))) => println!(
+        "{} at ({}, {}): nearest {} ({}), farthest {} ({})",
+        galaxy + 1,
+        row,
+        col,
+        nearest + 1,
+        near,
+        farthest + 1,
+        far
+      ),
+      _ => println!("{} at ({}, {}): no other galaxy", galaxy + 1, row, col),
+    }
+  }
+  Ok(())
+}
 
-  let (empty_rows, empty_cols) = get_empty_rows_and_cols(&map, nrow, ncol);
+// without a bucket size the distances are split into about 20 buckets
+fn print_histogram(galaxies: &GalaxyMap, bucket: Option<u64>) -> io::Result<()> {
+  const WIDTH: u64 = 50;
 
-  // find all galaxies
-  let galaxies = map
-    .iter()
-    .enumerate()
-    .filter(|(_, &value)| value == '#')
-    .map(|(idx, _)| idx)
-    .collect::<Vec<usize>>();
+  let bucket = bucket.unwrap_or_else(|| {
+    let max = (0..galaxies.galaxies.len())
+      .filter_map(|galaxy| galaxies.farthest(galaxy))
+      .map(|(_, distance)| distance)
+      .max()
+      .unwrap_or(0);
+    (max / 20).max(1)
+  });
+  let counts = galaxies.histogram(bucket);
+  let most = counts.values().copied().max().unwrap_or(0);
 
-  // find shortest paths between 2 galaxies
-  let mut total: u64 = 0;
-  for i in 0..galaxies.len() - 1 {
-    for j in i + 1..galaxies.len() {
-      let row1 = galaxies[i] / ncol;
-      let col1 = galaxies[i] % nrow;
-      let row2 = galaxies[j] / ncol;
-      let col2 = galaxies[j] % nrow;
-      let fromRow = row1.min(row2);
-      let toRow = row1.max(row2);
-      let fromCol = col1.min(col2);
-      let toCol = col1.max(col2);
-
-      for r in fromRow..toRow {
-        if empty_rows.contains(&(r as u32)) {
-          total += expansion_times;
-        } else {
-          total += 1;
-        }
-      }
+  for (&start, &count) in &counts {
+    let bar = (count * WIDTH).div_ceil(most) as usize;
+    println!(
+      "{:>10}..{:<10} {:>8} {}",
+      start,
+      start + bucket,
+      count,
+      "#".repeat(bar)
+    );
+  }
+  Ok(())
+}
 
-      for c in fromCol..toCol {
-        if empty_cols.contains(&(c as u32)) {
-          total += expansion_times;
-        } else {
-          total += 1;
-        }
-      }
+// coordinate of every line once each empty line counts `expansion_times`
+// times, as a running sum over the lines before it
+fn expanded_positions(len: usize, empty_lines: &[u32], expansion_times: u64) -> Vec<u64> {
+  let mut positions: Vec<u64> = Vec::with_capacity(len);
+  let mut position: u64 = 0;
+  let mut empty_lines = empty_lines.iter().peekable();
+  for line in 0..len {
+    positions.push(position);
+    if empty_lines
+      .next_if(|&&empty| empty as usize == line)
+      .is_some()
+    {
+      position += expansion_times;
+    } else {
+      position += 1;
     }
   }
+  positions
+}
 
-  Ok(total)
+// once sorted, the value at index i is the larger side of i pairs, so it
+// adds value * i minus the sum of the values before it
+fn sum_of_pairwise_gaps(mut values: Vec<u64>) -> u64 {
+  values.sort_unstable();
+  let mut total: u64 = 0;
+  let mut prefix: u64 = 0;
+  for (idx, &value) in values.iter().enumerate() {
+    total += value * idx as u64 - prefix;
+    prefix += value;
+  }
+  total
 }
 
 fn read_map(file: &str) -> io::Result<(Vec<char>, usize, usize)> {
@@ -70,7 +372,7 @@ fn read_map(file: &str) -> io::Result<(Vec<char>, usize, usize)> {
   Ok((map, nrow, ncol))
 }
 
-fn get_empty_rows_and_cols(map: &Vec<char>, nrow: usize, ncol: usize) -> (Vec<u32>, Vec<u32>) {
+fn get_empty_rows_and_cols(map: &[char], nrow: usize, ncol: usize) -> (Vec<u32>, Vec<u32>) {
   let mut empty_rows: Vec<u32> = Vec::new();
   let mut empty_cols: Vec<u32> = Vec::new();
 
@@ -102,3 +404,93 @@ fn get_empty_rows_and_cols(map: &Vec<char>, nrow: usize, ncol: usize) -> (Vec<u3
 
   (empty_rows, empty_cols)
 }
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::{Rng, SeedableRng};
+
+  fn brute_force(map: &[char], nrow: usize, ncol: usize, expansion_times: u64) -> u64 {
+    let is_empty_row = |row: usize| (0..ncol).all(|col| map[row * ncol + col] == '.');
+    let is_empty_col = |col: usize| (0..nrow).all(|row| map[row * ncol + col] == '.');
+    let galaxies: Vec<(usize, usize)> = (0..map.len())
+      .filter(|&idx| map[idx] == '#')
+      .map(|idx| (idx / ncol, idx % ncol))
+      .collect();
+
+    let mut total: u64 = 0;
+    for i in 0..galaxies.len() {
+      for j in i + 1..galaxies.len() {
+        let (row1, col1) = galaxies[i];
+        let (row2, col2) = galaxies[j];
+        for row in row1.min(row2)..row1.max(row2) {
+          total += if is_empty_row(row) {
+            expansion_times
+          } else {
+            1
+          };
+        }
+        for col in col1.min(col2)..col1.max(col2) {
+          total += if is_empty_col(col) {
+            expansion_times
+          } else {
+            1
+          };
+        }
+      }
+    }
+    total
+  }
+
+  #[test]
+  fn test_prefix_sums_match_brute_force_on_rectangular_maps() {
+    let mut rng = StdRng::seed_from_u64(11);
+    for (nrow, ncol) in [(1, 9), (7, 3), (12, 25), (30, 8), (17, 17)] {
+      let map: Vec<char> = (0..nrow * ncol)
+        .map(|_| if rng.gen_ratio(1, 6) { '#' } else { '.' })
+        .collect();
+      for expansion_times in [0, 1, 2, 10, 1000000] {
+        let galaxies = GalaxyMap::build(
+          &map,
+          nrow,
+          ncol,
+          expansion_times,
+          expansion_times,
+          Metric::Manhattan,
+        );
+        assert_eq!(
+          galaxies.sum_of_distances(),
+          brute_force(&map, nrow, ncol, expansion_times)
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_sum_matches_pairwise_distances() {
+    let mut rng = StdRng::seed_from_u64(49);
+    for (nrow, ncol) in [(9, 14), (20, 6)] {
+      let map: Vec<char> = (0..nrow * ncol)
+        .map(|_| if rng.gen_ratio(1, 5) { '#' } else { '.' })
+        .collect();
+      for metric in [Metric::Manhattan, Metric::Chebyshev] {
+        let galaxies = GalaxyMap::build(&map, nrow, ncol, 3, 100, metric);
+        let n = galaxies.galaxies.len();
+        let pairwise: u64 = (0..n)
+          .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+          .map(|(a, b)| galaxies.distance(a, b))
+          .sum();
+        assert_eq!(galaxies.sum_of_distances(), pairwise);
+        assert_eq!(
+          galaxies.histogram(7).values().sum::<u64>(),
+          (n * (n - 1) / 2) as u64
+        );
+      }
+    }
+  }
+}
@@ -1,201 +1,331 @@
-use std::collections::HashMap;
+use num_bigint::BigUint;
 use std::fs;
 use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
 
-#[derive(Hash, Eq, PartialEq, Debug)]
-struct Record {
-  condition: Vec<char>,
-  numbers: Vec<u64>,
-}
+const DEFAULT_FILE: &str = "inputs/day12/part1";
 
-pub fn solve() {
-  println!("Part 1: {}", part1("inputs/day12/part1").unwrap());
-  println!("Part 2: {}", part2("inputs/day12/part1").unwrap());
+// arrangement counts, u128 for speed with BigUint to fall back on when a
+// large unfold factor overflows it
+trait Count: Clone {
+  fn zero() -> Self;
+  fn one() -> Self;
+  fn add(&self, other: &Self) -> Option<Self>;
 }
 
-fn part1(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
+impl Count for u128 {
+  fn zero() -> u128 {
+    0
+  }
 
-  let mut total: u64 = 0;
-  for line in input.lines() {
-    let parts = line.split_whitespace().collect::<Vec<&str>>();
-    let condition = parts[0].chars().collect::<Vec<char>>();
-    let numbers = parts[1]
-      .split(",")
-      .map(|s| s.parse::<u64>().unwrap())
-      .collect::<Vec<u64>>();
-    let mut memory: HashMap<Record, u64> = HashMap::new();
-    let condition = normalize_condition(&condition);
-    total += count_arrangement(condition, &numbers, &mut memory, 0, "");
+  fn one() -> u128 {
+    1
   }
 
-  Ok(total)
+  fn add(&self, other: &u128) -> Option<u128> {
+    self.checked_add(*other)
+  }
 }
 
-fn part2(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
+impl Count for BigUint {
+  fn zero() -> BigUint {
+    BigUint::from(0u8)
+  }
 
-  let mut total: u64 = 0;
-  for line in input.lines() {
-    let parts = line.split_whitespace().collect::<Vec<&str>>();
-    let condition = parts[0].chars().collect::<Vec<char>>();
-    let condition = unfold_condition(&condition);
-    let numbers = parts[1]
-      .split(",")
-      .map(|s| s.parse::<u64>().unwrap())
-      .collect::<Vec<u64>>();
-    let numbers = unfold_numbers(&numbers);
-    let mut memory: HashMap<Record, u64> = HashMap::new();
-    let condition = normalize_condition(&condition);
-    total += count_arrangement(condition, &numbers, &mut memory, 0, "");
+  fn one() -> BigUint {
+    BigUint::from(1u8)
   }
 
-  Ok(total)
+  fn add(&self, other: &BigUint) -> Option<BigUint> {
+    Some(self + other)
+  }
 }
 
-fn unfold_condition(condition: &Vec<char>) -> Vec<char> {
-  let mut new_condition = condition.clone();
-  new_condition.push('?');
-  for _ in 0..3 {
-    new_condition.extend(condition.clone());
-    new_condition.push('?');
-  }
-  new_condition.extend(condition.clone());
-  new_condition
+struct SpringRow {
+  springs: Vec<u8>,
+  groups: Vec<usize>,
 }
 
-fn unfold_numbers(numbers: &Vec<u64>) -> Vec<u64> {
-  let mut new_numbers = numbers.clone();
-  for _ in 0..4 {
-    new_numbers.extend(numbers.clone());
-  }
-  new_numbers
-}
-
-fn count_arrangement<'a, 'b>(
-  condition: &'a [char],
-  numbers: &'b [u64],
-  memory: &mut HashMap<Record, u64>,
-  recursive_level: u8,
-  tag: &str,
-) -> u64 {
-  // base cases
-  if condition.len() < numbers.len() {
-    return 0;
-  }
+impl SpringRow {
+  fn parse(line: &str) -> io::Result<SpringRow> {
+    let (springs, groups) = line
+      .split_once(' ')
+      .ok_or_else(|| invalid_data(format!("missing groups in `{}`", line)))?;
+    if let Some(c) = springs.chars().find(|c| !matches!(c, '.' | '#' | '?')) {
+      return Err(invalid_data(format!("unknown spring `{}`", c)));
+    }
+    let groups = groups
+      .split(',')
+      .map(|s| {
+        s.trim()
+          .parse::<usize>()
+          .ok()
+          .filter(|&len| len > 0)
+          .ok_or_else(|| invalid_data(format!("malformed group `{}`", s)))
+      })
+      .collect::<io::Result<Vec<usize>>>()?;
 
-  if condition.len() == 0 && numbers.len() == 0 {
-    return 1;
+    Ok(SpringRow {
+      springs: springs.as_bytes().to_vec(),
+      groups,
+    })
   }
 
-  if numbers.len() == 0 {
-    for &c in condition {
-      if c == '#' {
-        return 0;
+  // arrangements of the row unfolded `unfold` times, the copies joined by
+  // `?`, without building the unfolded row: position p reads the original
+  // spring p % (len + 1), the joint where that is len, and group j reads
+  // group j % groups
+  //
+  // ways[i][j] counts the arrangements of springs i.. with groups j.., row
+  // n + 1 stands in for the position past the separator after the last
+  // spring, so the table is the only allocation; None when a count
+  // overflows T
+  fn count_arrangements<T: Count>(&self, unfold: usize) -> Option<T> {
+    let block = self.springs.len() + 1;
+    let n = unfold * block - 1;
+    let g = unfold * self.groups.len();
+    let spring = |p: usize| {
+      if p % block == block - 1 {
+        b'?'
+      } else {
+        self.springs[p % block]
       }
-    }
-    return 1;
-  }
+    };
+    let width = g + 1;
 
-  // check in memory
-  let record = Record {
-    condition: condition.to_vec(),
-    numbers: numbers.to_vec(),
-  };
-  if memory.contains_key(&record) {
-    return *memory.get(&record).unwrap();
-  }
+    let mut ways: Vec<T> = vec![T::zero(); (n + 2) * width];
+    ways[n * width + g] = T::one();
+    ways[(n + 1) * width + g] = T::one();
 
-  let number = numbers[0];
-  let mut total = 0;
-  let mut cont_cnt = 0;
-
-  let mut i = 0;
-  let mut is_break = false;
-  while i < condition.len() {
-    if cont_cnt > 0 {
-      // counting
-      if condition[i] == '#' {
-        cont_cnt += 1;
-        if cont_cnt > number {
-          is_break = true;
-          break;
-        }
-        i += 1
-      } else if condition[i] == '?' {
-        if cont_cnt == number {
-          let new_condition = normalize_condition(&condition[i + 1..]);
-          total += count_arrangement(
-            &new_condition,
-            &numbers[1..],
-            memory,
-            recursive_level + 1,
-            "1",
-          );
-          is_break = true;
-          break;
+    // nearest operational spring at or after i, a group starting at i must
+    // end before it
+    let mut next_dot = n;
+    for i in (0..n).rev() {
+      let c = spring(i);
+      if c == b'.' {
+        next_dot = i;
+      }
+      for j in 0..=g {
+        let mut count = if c != b'#' {
+          ways[(i + 1) * width + j].clone()
         } else {
-          // cont_cnt < number -> continue to count
-          cont_cnt += 1;
-          i += 1;
-        }
-      } else {
-        if cont_cnt == number {
-          let new_condition = normalize_condition(&condition[i + 1..]);
-          total += count_arrangement(
-            &new_condition,
-            &numbers[1..],
-            memory,
-            recursive_level + 1,
-            "2",
-          );
+          T::zero()
+        };
+        if c != b'.' && j < g {
+          let end = i + self.groups[j % self.groups.len()];
+          if end <= next_dot && (end == n || spring(end) != b'#') {
+            count = count.add(&ways[(end + 1) * width + j + 1])?;
+          }
         }
-        is_break = true;
-        break;
-      }
-    } else {
-      if condition[i] == '#' {
-        cont_cnt += 1;
-        i += 1;
-      } else if condition[i] == '?' {
-        // case 1: replace with '.'
-        let new_condition = normalize_condition(&condition[i + 1..]);
-        total += count_arrangement(&new_condition, &numbers, memory, recursive_level + 1, "3");
-
-        // case 2: replace with '#'
-        let mut new_condition = condition[i..].to_vec();
-        new_condition[0] = '#';
-        total += count_arrangement(&new_condition, &numbers, memory, recursive_level + 1, "4");
-        is_break = true;
-        break;
-      } else {
-        i += 1;
+        ways[i * width + j] = count;
       }
     }
+
+    Some(ways.swap_remove(0))
   }
 
-  if !is_break && cont_cnt == number && numbers.len() == 1 {
-    total += 1;
+  fn count_exact(&self, unfold: usize) -> BigUint {
+    match self.count_arrangements::<u128>(unfold) {
+      Some(count) => BigUint::from(count),
+      None => self.count_arrangements::<BigUint>(unfold).unwrap(),
+    }
   }
+}
 
-  memory.insert(record, total);
-  total
+pub fn solve() {
+  println!("Part 1: {}", part1(DEFAULT_FILE).unwrap());
+  println!("Part 2: {}", part2(DEFAULT_FILE).unwrap());
 }
 
-fn normalize_condition(condition: &[char]) -> &[char] {
-  if condition.len() == 0 {
-    return condition;
+// day12 count [--unfold N] [--threads N] [file]
+pub fn run(args: &[String]) {
+  match args.first().map(|s| s.as_str()) {
+    None => solve(),
+    Some("count") => {
+      if let Err(err) = print_count(&args[1..]) {
+        println!("Error: {}", err);
+      }
+    }
+    Some(cmd) => println!("Unknown day12 command: {}", cmd),
   }
+}
+
+fn part1(file: &str) -> io::Result<BigUint> {
+  Ok(total_arrangements(&parse_rows(file)?, 1, default_threads()))
+}
 
-  let mut head = 0;
-  while head < condition.len() && condition[head] == '.' {
-    head += 1;
+fn part2(file: &str) -> io::Result<BigUint> {
+  Ok(total_arrangements(&parse_rows(file)?, 5, default_threads()))
+}
+
+fn parse_rows(file: &str) -> io::Result<Vec<SpringRow>> {
+  let input = fs::read_to_string(file)?;
+  input
+    .lines()
+    .enumerate()
+    .map(|(idx, line)| {
+      SpringRow::parse(line).map_err(|err| invalid_data(format!("line {}: {}", idx + 1, err)))
+    })
+    .collect()
+}
+
+fn default_threads() -> usize {
+  thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// each worker keeps taking the next row that nobody has started, so a few
+// expensive rows do not hold up a whole chunk
+fn total_arrangements(rows: &[SpringRow], unfold: usize, threads: usize) -> BigUint {
+  let next_row = AtomicUsize::new(0);
+  thread::scope(|scope| {
+    let workers: Vec<_> = (0..threads.max(1))
+      .map(|_| {
+        scope.spawn(|| {
+          let mut total = BigUint::from(0u8);
+          while let Some(row) = rows.get(next_row.fetch_add(1, Ordering::Relaxed)) {
+            total += row.count_exact(unfold);
+          }
+          total
+        })
+      })
+      .collect();
+    workers
+      .into_iter()
+      .map(|worker| worker.join().unwrap())
+      .sum()
+  })
+}
+
+fn print_count(args: &[String]) -> io::Result<()> {
+  let mut unfold: usize = 1;
+  let mut threads = default_threads();
+  let mut file = DEFAULT_FILE;
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    let mut value = || {
+      iter
+        .next()
+        .ok_or_else(|| invalid_input(format!("missing value for {}", arg)))
+    };
+    let positive = |text: &String| {
+      text
+        .parse::<usize>()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| invalid_input(format!("expected a positive number, found `{}`", text)))
+    };
+    match arg.as_str() {
+      "--unfold" => unfold = positive(value()?)?,
+      "--threads" => threads = positive(value()?)?,
+      _ => file = arg,
+    }
+  }
+
+  let rows = parse_rows(file)?;
+  let started = Instant::now();
+  let total = total_arrangements(&rows, unfold, threads);
+  println!(
+    "{} rows unfolded {} times: {} arrangements ({:?} on {} threads)",
+    rows.len(),
+    unfold,
+    total,
+    started.elapsed(),
+    threads
+  );
+  Ok(())
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // tries every way to fill in the unknown springs of the unfolded row
+  fn brute_force(row: &SpringRow, unfold: usize) -> u128 {
+    let mut springs: Vec<u8> = Vec::new();
+    let mut groups: Vec<usize> = Vec::new();
+    for copy in 0..unfold {
+      if copy > 0 {
+        springs.push(b'?');
+      }
+      springs.extend(&row.springs);
+      groups.extend(&row.groups);
+    }
+
+    let unknown: Vec<usize> = (0..springs.len())
+      .filter(|&idx| springs[idx] == b'?')
+      .collect();
+    let mut count = 0;
+    for mask in 0..1u64 << unknown.len() {
+      for (bit, &idx) in unknown.iter().enumerate() {
+        springs[idx] = if mask >> bit & 1 == 1 { b'#' } else { b'.' };
+      }
+      let filled: Vec<usize> = springs
+        .split(|&c| c == b'.')
+        .map(|run| run.len())
+        .filter(|&len| len > 0)
+        .collect();
+      if filled == groups {
+        count += 1;
+      }
+    }
+    count
   }
 
-  let mut tail: i32 = condition.len() as i32 - 1;
-  while tail > head as i32 && condition[tail as usize] == '.' {
-    tail -= 1;
+  #[test]
+  fn test_table_matches_brute_force() {
+    let lines = [
+      "???.### 1,1,3",
+      ".??..??...?##. 1,1,3",
+      "?#?#?#?#?#?#?#? 1,3,1,6",
+      "????.#...#... 4,1,1",
+      "????.######..#####. 1,6,5",
+      "?###???????? 3,2,1",
+      "# 1",
+      "? 1",
+      "#.#? 1,2",
+      "?.?#? 2",
+    ];
+    for line in lines {
+      let row = SpringRow::parse(line).unwrap();
+      assert_eq!(
+        row.count_arrangements(1),
+        Some(brute_force(&row, 1)),
+        "{}",
+        line
+      );
+      if row.springs.iter().filter(|&&c| c == b'?').count() <= 5 {
+        assert_eq!(
+          row.count_arrangements(2),
+          Some(brute_force(&row, 2)),
+          "{}",
+          line
+        );
+      }
+    }
+
+    let rows: Vec<SpringRow> = lines[..6]
+      .iter()
+      .map(|line| SpringRow::parse(line).unwrap())
+      .collect();
+    assert_eq!(total_arrangements(&rows, 1, 3), BigUint::from(21u8));
+    assert_eq!(total_arrangements(&rows, 5, 4), BigUint::from(525152u32));
+
+    // a count past u128 is finished with big integers
+    let row = SpringRow::parse("???????? 1").unwrap();
+    assert_eq!(row.count_arrangements::<u128>(60), None);
+    let exact = row.count_exact(60);
+    assert!(exact > BigUint::from(u128::MAX));
+    assert_eq!(Some(exact), row.count_arrangements::<BigUint>(60));
   }
-  tail = tail.max(head as i32);
-  &condition[head..tail as usize + 1]
 }
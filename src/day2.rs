@@ -1,53 +1,398 @@
-use regex::Regex;
 use std::fs;
 use std::io;
 
+const DEFAULT_FILE: &str = "inputs/day2/part1";
+const DEFAULT_BAG: &str = "12 red, 13 green, 14 blue";
+
+struct Palette {
+  colors: Vec<String>,
+}
+
+impl Palette {
+  fn new(colors: &str) -> io::Result<Palette> {
+    let mut palette = Palette { colors: Vec::new() };
+    for color in colors.split(',').map(|c| c.trim()) {
+      if color.is_empty() {
+        return Err(invalid_input(format!(
+          "empty color in palette `{}`",
+          colors
+        )));
+      }
+      if palette.index(color).is_some() {
+        return Err(invalid_input(format!(
+          "duplicate color `{}` in palette",
+          color
+        )));
+      }
+      palette.colors.push(color.to_string());
+    }
+    Ok(palette)
+  }
+
+  fn index(&self, color: &str) -> Option<usize> {
+    self.colors.iter().position(|c| c == color)
+  }
+}
+
+impl Default for Palette {
+  fn default() -> Palette {
+    Palette {
+      colors: vec![
+        String::from("red"),
+        String::from("green"),
+        String::from("blue"),
+      ],
+    }
+  }
+}
+
+// cube counts indexed by the colours of a palette
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CubeSet {
+  counts: Vec<u64>,
+}
+
+#[derive(Debug)]
+struct Game {
+  id: u32,
+  draws: Vec<CubeSet>,
+}
+
+impl Game {
+  fn parse(line: &str, palette: &Palette) -> io::Result<Game> {
+    let (header, draws) = line
+      .split_once(':')
+      .ok_or_else(|| invalid_data(format!("missing `:` in `{}`", line)))?;
+    let id = header
+      .strip_prefix("Game ")
+      .and_then(|id| id.trim().parse::<u32>().ok())
+      .ok_or_else(|| invalid_data(format!("malformed game header `{}`", header)))?;
+    let draws = draws
+      .split(';')
+      .map(|draw| CubeSet::parse(draw, palette))
+      .collect::<io::Result<Vec<CubeSet>>>()?;
+
+    Ok(Game { id, draws })
+  }
+
+  // smallest bag that could have produced every draw of the game
+  fn min_bag(&self) -> CubeSet {
+    self.draws[1..]
+      .iter()
+      .fold(self.draws[0].clone(), |acc, draw| acc.max(draw))
+  }
+
+  fn is_possible_with(&self, bag: &CubeSet) -> bool {
+    self.draws.iter().all(|draw| bag.contains(draw))
+  }
+}
+
+impl CubeSet {
+  fn empty(palette: &Palette) -> CubeSet {
+    CubeSet {
+      counts: vec![0; palette.colors.len()],
+    }
+  }
+
+  fn parse(text: &str, palette: &Palette) -> io::Result<CubeSet> {
+    let mut set = CubeSet::empty(palette);
+    for cubes in text.split(',') {
+      let (count, color) = cubes
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| invalid_data(format!("malformed cubes `{}`", cubes.trim())))?;
+      let count = count
+        .parse::<u64>()
+        .map_err(|_| invalid_data(format!("malformed count `{}`", count)))?;
+      let color = color.trim();
+      let idx = palette
+        .index(color)
+        .ok_or_else(|| invalid_data(format!("unknown color `{}`", color)))?;
+      set.counts[idx] = set.counts[idx]
+        .checked_add(count)
+        .ok_or_else(|| invalid_data(format!("too many {} cubes", color)))?;
+    }
+    Ok(set)
+  }
+
+  fn max(&self, other: &CubeSet) -> CubeSet {
+    CubeSet {
+      counts: self
+        .counts
+        .iter()
+        .zip(other.counts.iter())
+        .map(|(a, b)| *a.max(b))
+        .collect(),
+    }
+  }
+
+  fn contains(&self, other: &CubeSet) -> bool {
+    self
+      .counts
+      .iter()
+      .zip(other.counts.iter())
+      .all(|(a, b)| a >= b)
+  }
+
+  fn power(&self) -> io::Result<u64> {
+    self
+      .counts
+      .iter()
+      .try_fold(1_u64, |acc, &count| acc.checked_mul(count))
+      .ok_or_else(|| invalid_data(format!("power of {:?} overflows u64", self.counts)))
+  }
+
+  fn format(&self, palette: &Palette) -> String {
+    self
+      .counts
+      .iter()
+      .zip(palette.colors.iter())
+      .map(|(count, color)| format!("{} {}", count, color))
+      .collect::<Vec<String>>()
+      .join(", ")
+  }
+}
+
 pub fn solve() {
-  println!("Part 1: {}", part1("inputs/day2/part1").unwrap());
-  println!("Part 2: {}", part2("inputs/day2/part1").unwrap());
+  println!("Part 1: {}", part1(DEFAULT_FILE).unwrap());
+  println!("Part 2: {}", part2(DEFAULT_FILE).unwrap());
 }
 
-fn part1(file: &str) -> io::Result<u32> {
-  let input = fs::read_to_string(file)?;
-  let mut total: u32 = 0;
+// day2 [--palette red,green,blue] possible [bag] [file]
+// day2 [--palette red,green,blue] min-bag [file]
+// day2 [--palette red,green,blue] best-cube [bag] [file]
+pub fn run(args: &[String]) {
+  let result = parse_options(args).and_then(|(palette, positional)| match positional.as_slice() {
+    [] => {
+      solve();
+      Ok(())
+    }
+    ["possible", rest @ ..] => print_possible(&palette, rest),
+    ["min-bag", rest @ ..] => print_min_bag(&palette, rest),
+    ["best-cube", rest @ ..] => print_best_cube(&palette, rest),
+    [cmd, ..] => Err(invalid_data(format!("unknown day2 command `{}`", cmd))),
+  });
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
 
-  for (idx, line) in input.lines().enumerate() {
-    let (red, green, blue) = get_max_colors(line);
-    if red <= 12 && green <= 13 && blue <= 14 {
-      total += (idx + 1) as u32;
+// [--palette red,green,blue,...], everything else is positional
+fn parse_options(args: &[String]) -> io::Result<(Palette, Vec<&str>)> {
+  let mut palette = Palette::default();
+  let mut positional: Vec<&str> = Vec::new();
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    if arg == "--palette" {
+      let colors = iter
+        .next()
+        .ok_or_else(|| invalid_input(format!("missing value for {}", arg)))?;
+      palette = Palette::new(colors)?;
+    } else {
+      positional.push(arg);
     }
   }
 
-  Ok(total)
+  Ok((palette, positional))
 }
 
-fn part2(file: &str) -> io::Result<u32> {
-  let input = fs::read_to_string(file)?;
-  let mut total: u32 = 0;
+fn part1(file: &str) -> io::Result<u32> {
+  let palette = Palette::default();
+  let games = parse_games(file, &palette)?;
+  let bag = CubeSet::parse(DEFAULT_BAG, &palette)?;
+
+  Ok(possible_games(&games, &bag).iter().sum())
+}
 
-  for line in input.lines() {
-    let (red, green, blue) = get_max_colors(line);
-    total += red * green * blue;
+fn part2(file: &str) -> io::Result<u64> {
+  let games = parse_games(file, &Palette::default())?;
+  let mut total: u64 = 0;
+
+  for game in games {
+    total = total
+      .checked_add(game.min_bag().power()?)
+      .ok_or_else(|| invalid_data(String::from("total power overflows u64")))?;
   }
 
   Ok(total)
 }
 
-fn get_max_colors(line: &str) -> (u32, u32, u32) {
-  let mut red: u32 = 0;
-  let mut green: u32 = 0;
-  let mut blue: u32 = 0;
-  let re = Regex::new(r"(\d+ red|\d+ green|\d+ blue)").unwrap();
-  for mat in re.find_iter(line) {
-    let mut split = mat.as_str().split_whitespace();
-    let number = split.next().unwrap().parse::<u32>().unwrap();
-    let color = split.next().unwrap().trim();
-    match color {
-      "red" => red = if red < number { number } else { red },
-      "green" => green = if green < number { number } else { green },
-      "blue" => blue = if blue < number { number } else { blue },
-      _ => panic!("Unknown color: {}", color),
+fn print_possible(palette: &Palette, args: &[&str]) -> io::Result<()> {
+  let bag = CubeSet::parse(args.first().unwrap_or(&DEFAULT_BAG), palette)?;
+  let games = parse_games(args.get(1).unwrap_or(&DEFAULT_FILE), palette)?;
+  let ids = possible_games(&games, &bag);
+
+  println!("Bag: {}", bag.format(palette));
+  println!(
+    "Possible games ({} of {}): {:?}",
+    ids.len(),
+    games.len(),
+    ids
+  );
+  println!("Sum of ids: {}", ids.iter().sum::<u32>());
+  Ok(())
+}
+
+fn print_min_bag(palette: &Palette, args: &[&str]) -> io::Result<()> {
+  let games = parse_games(args.first().unwrap_or(&DEFAULT_FILE), palette)?;
+
+  println!(
+    "Minimum bag: {}",
+    min_bag_for_all(&games, palette).format(palette)
+  );
+  Ok(())
+}
+
+fn print_best_cube(palette: &Palette, args: &[&str]) -> io::Result<()> {
+  let bag = CubeSet::parse(args.first().unwrap_or(&DEFAULT_BAG), palette)?;
+  let games = parse_games(args.get(1).unwrap_or(&DEFAULT_FILE), palette)?;
+
+  match best_extra_cube(&games, &bag) {
+    Some((color, ids)) => println!(
+      "One more {} cube unlocks {} games: {:?}",
+      palette.colors[color],
+      ids.len(),
+      ids
+    ),
+    None => println!("No single extra cube unlocks another game"),
+  }
+  Ok(())
+}
+
+fn possible_games(games: &[Game], bag: &CubeSet) -> Vec<u32> {
+  games
+    .iter()
+    .filter(|game| game.is_possible_with(bag))
+    .map(|game| game.id)
+    .collect()
+}
+
+fn min_bag_for_all(games: &[Game], palette: &Palette) -> CubeSet {
+  games.iter().fold(CubeSet::empty(palette), |acc, game| {
+    acc.max(&game.min_bag())
+  })
+}
+
+// returns the colour whose extra cube makes the most impossible games
+// possible, together with the ids of those games
+fn best_extra_cube(games: &[Game], bag: &CubeSet) -> Option<(usize, Vec<u32>)> {
+  let impossible: Vec<&Game> = games
+    .iter()
+    .filter(|game| !game.is_possible_with(bag))
+    .collect();
+  let mut best: Option<(usize, Vec<u32>)> = None;
+
+  for color in 0..bag.counts.len() {
+    let mut bigger_bag = bag.clone();
+    match bag.counts[color].checked_add(1) {
+      Some(count) => bigger_bag.counts[color] = count,
+      None => continue,
+    }
+    let unlocked: Vec<u32> = impossible
+      .iter()
+      .filter(|game| game.is_possible_with(&bigger_bag))
+      .map(|game| game.id)
+      .collect();
+    if !unlocked.is_empty() && best.as_ref().is_none_or(|b| unlocked.len() > b.1.len()) {
+      best = Some((color, unlocked));
     }
   }
-  (red, green, blue)
+
+  best
+}
+
+fn parse_games(file: &str, palette: &Palette) -> io::Result<Vec<Game>> {
+  let input = fs::read_to_string(file)?;
+  input
+    .lines()
+    .enumerate()
+    .map(|(idx, line)| {
+      Game::parse(line, palette).map_err(|err| invalid_data(format!("line {}: {}", idx + 1, err)))
+    })
+    .collect()
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse_error(line: &str) -> String {
+    Game::parse(line, &Palette::default())
+      .unwrap_err()
+      .to_string()
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    let game = Game::parse("Game 7: 3 blue, 4 red; 2 green", &Palette::default()).unwrap();
+    assert_eq!(game.id, 7);
+    assert_eq!(game.draws.len(), 2);
+    assert_eq!(game.draws[0].counts, vec![4, 0, 3]);
+
+    assert_eq!(parse_error("Game 1: 3 purple"), "unknown color `purple`");
+    assert_eq!(parse_error("Game 1: x red"), "malformed count `x`");
+    assert_eq!(parse_error("Game 1: -3 red"), "malformed count `-3`");
+    assert_eq!(parse_error("Game 1: red"), "malformed cubes `red`");
+    assert_eq!(
+      parse_error("Round 1: 3 red"),
+      "malformed game header `Round 1`"
+    );
+    assert_eq!(
+      parse_error("Game one: 3 red"),
+      "malformed game header `Game one`"
+    );
+    assert_eq!(parse_error("Game 1 3 red"), "missing `:` in `Game 1 3 red`");
+  }
+
+  #[test]
+  fn test_game_queries_on_sample() {
+    let palette = Palette::default();
+    let games = parse_games("inputs/day2/sample1", &palette).unwrap();
+    let bag = |text: &str| CubeSet::parse(text, &palette).unwrap();
+
+    assert_eq!(possible_games(&games, &bag(DEFAULT_BAG)), vec![1, 2, 5]);
+    assert_eq!(part1("inputs/day2/sample1").unwrap(), 8);
+    assert_eq!(part2("inputs/day2/sample1").unwrap(), 2286);
+    assert_eq!(min_bag_for_all(&games, &palette).counts, vec![20, 13, 15]);
+
+    // one red cube unlocks game 3 and one blue cube game 4, the first colour wins
+    assert_eq!(
+      best_extra_cube(&games, &bag("19 red, 13 green, 14 blue")),
+      Some((0, vec![3]))
+    );
+    assert_eq!(
+      best_extra_cube(&games, &bag("20 red, 13 green, 14 blue")),
+      Some((2, vec![4]))
+    );
+    assert_eq!(best_extra_cube(&games, &bag(DEFAULT_BAG)), None);
+  }
+
+  #[test]
+  fn test_custom_palettes() {
+    let palette = Palette::new("cyan, magenta").unwrap();
+    let game = Game::parse("Game 1: 2 magenta, 1 cyan; 3 magenta", &palette).unwrap();
+    assert_eq!(game.min_bag().counts, vec![1, 3]);
+    assert_eq!(game.min_bag().power().unwrap(), 3);
+    assert!(Game::parse("Game 1: 2 red", &palette).is_err());
+
+    assert!(Palette::new("").is_err());
+    assert!(Palette::new("red,,blue").is_err());
+    assert!(Palette::new("red, blue, red").is_err());
+
+    let huge = CubeSet {
+      counts: vec![u64::MAX, 2],
+    };
+    assert!(huge.power().is_err());
+    assert!(CubeSet::parse("18446744073709551615 cyan, 1 cyan", &palette).is_err());
+  }
 }
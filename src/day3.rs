@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::fs;
 use std::io;
 
@@ -25,101 +24,199 @@ impl Grid {
     Ok(Grid { data, nrow, ncol })
   }
 
-  fn is_symbol(&self, row: i32, col: i32) -> bool {
-    if row < 0 || col < 0 {
-      return false;
-    }
-    if row >= self.nrow as i32 || col >= self.ncol as i32 {
-      return false;
-    }
-    let idx = (row as usize) * self.ncol + (col as usize);
-    if self.data[idx] != '.' && !self.data[idx].is_ascii_digit() {
-      return true;
-    }
-    false
+  fn get(&self, row: usize, col: usize) -> char {
+    self.data[row * self.ncol + col]
   }
+}
 
-  fn is_gear(&self, row: i32, col: i32) -> bool {
-    if row < 0 || col < 0 {
-      return false;
-    }
-    if row >= self.nrow as i32 || col >= self.ncol as i32 {
-      return false;
-    }
-    let idx = (row as usize) * self.ncol + (col as usize);
-    self.data[idx] == '*'
-  }
+#[derive(Debug)]
+struct NumberToken {
+  value: u32,
+  row: usize,
+  start_col: usize,
+  end_col: usize, // inclusive
+}
+
+#[derive(Debug)]
+struct Symbol {
+  value: char,
+  row: usize,
+  col: usize,
+}
 
-  fn is_adjacent_to_a_symbol(&self, row: i32, start_col: i32, end_col: i32) -> bool {
-    let mut adjacent;
+// bipartite adjacency between the numbers and the symbols of a schematic,
+// both sides refer to each other by their index in `numbers`/`symbols`
+struct Schematic {
+  numbers: Vec<NumberToken>,
+  symbols: Vec<Symbol>,
+  number_to_symbols: Vec<Vec<usize>>,
+  symbol_to_numbers: Vec<Vec<usize>>,
+}
+
+impl Schematic {
+  fn extract(grid: &Grid) -> io::Result<Schematic> {
+    let mut numbers: Vec<NumberToken> = Vec::new();
+    let mut symbols: Vec<Symbol> = Vec::new();
+    // symbol index by grid cell, so adjacency is a lookup instead of a rescan
+    let mut symbol_at: Vec<Option<usize>> = vec![None; grid.data.len()];
 
-    for c in start_col..end_col + 1 {
-      if c == start_col {
-        adjacent = self.is_symbol(row - 1, c - 1)
-          || self.is_symbol(row, c - 1)
-          || self.is_symbol(row + 1, c - 1);
-        if adjacent {
-          return true;
+    for row in 0..grid.nrow {
+      let mut current: Option<NumberToken> = None;
+      for col in 0..grid.ncol {
+        let c = grid.get(row, col);
+        if let Some(digit) = c.to_digit(10) {
+          let token = current.get_or_insert(NumberToken {
+            value: 0,
+            row,
+            start_col: col,
+            end_col: col,
+          });
+          token.value = token
+            .value
+            .checked_mul(10)
+            .and_then(|value| value.checked_add(digit))
+            .ok_or_else(|| {
+              io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                  "number at ({}, {}) does not fit in u32",
+                  row, token.start_col
+                ),
+              )
+            })?;
+          token.end_col = col;
+          continue;
         }
-      }
 
-      adjacent = self.is_symbol(row - 1, c) || self.is_symbol(row + 1, c);
-      if adjacent {
-        return true;
+        numbers.extend(current.take());
+        if c != '.' {
+          symbol_at[row * grid.ncol + col] = Some(symbols.len());
+          symbols.push(Symbol { value: c, row, col });
+        }
       }
+      numbers.extend(current.take());
+    }
+
+    let mut number_to_symbols: Vec<Vec<usize>> = vec![Vec::new(); numbers.len()];
+    let mut symbol_to_numbers: Vec<Vec<usize>> = vec![Vec::new(); symbols.len()];
+
+    for (number_idx, number) in numbers.iter().enumerate() {
+      let from_row = number.row.saturating_sub(1);
+      let to_row = (number.row + 1).min(grid.nrow - 1);
+      let from_col = number.start_col.saturating_sub(1);
+      let to_col = (number.end_col + 1).min(grid.ncol - 1);
 
-      if c == end_col {
-        adjacent = self.is_symbol(row - 1, c + 1)
-          || self.is_symbol(row, c + 1)
-          || self.is_symbol(row + 1, c + 1);
-        if adjacent {
-          return true;
+      for row in from_row..=to_row {
+        for col in from_col..=to_col {
+          if let Some(symbol_idx) = symbol_at[row * grid.ncol + col] {
+            number_to_symbols[number_idx].push(symbol_idx);
+            symbol_to_numbers[symbol_idx].push(number_idx);
+          }
         }
       }
     }
 
-    false
+    Ok(Schematic {
+      numbers,
+      symbols,
+      number_to_symbols,
+      symbol_to_numbers,
+    })
   }
 
-  fn is_ascii_digit(&self, row: i32, col: i32) -> bool {
-    let idx = (row as usize) * self.ncol + (col as usize);
-    self.data[idx].is_ascii_digit()
+  fn numbers_of_symbol(&self, symbol_idx: usize) -> impl Iterator<Item = &NumberToken> {
+    self.symbol_to_numbers[symbol_idx]
+      .iter()
+      .map(|&idx| &self.numbers[idx])
   }
 
-  fn get_adjacent_gears(&self, row: i32, start_col: i32, end_col: i32) -> Vec<u32> {
-    let mut gears: Vec<u32> = Vec::new();
+  fn symbols_of_number(&self, number_idx: usize) -> impl Iterator<Item = &Symbol> {
+    self.number_to_symbols[number_idx]
+      .iter()
+      .map(|&idx| &self.symbols[idx])
+  }
 
-    for c in start_col..end_col + 1 {
-      if c == start_col {
-        for r in row - 1..row + 2 {
-          if self.is_gear(r, c - 1) {
-            let idx = (r as u32) * (self.ncol as u32) + (c as u32 - 1);
-            gears.push(idx);
-          }
-        }
-      }
+  fn orphan_numbers(&self) -> impl Iterator<Item = &NumberToken> {
+    self
+      .numbers
+      .iter()
+      .enumerate()
+      .filter(|(idx, _)| self.number_to_symbols[*idx].is_empty())
+      .map(|(_, number)| number)
+  }
+}
 
-      if c == end_col {
-        for r in row - 1..row + 2 {
-          if self.is_gear(r, c + 1) {
-            let idx = (r as u32) * (self.ncol as u32) + (c as u32 + 1);
-            gears.push(idx);
-          }
-        }
-      }
+#[derive(Clone, Copy)]
+enum Aggregate {
+  Product,
+  Sum,
+  Max,
+}
 
-      if self.is_gear(row - 1, c) {
-        let idx = ((row - 1) as u32) * (self.ncol as u32) + (c as u32);
-        gears.push(idx);
-      }
+impl Aggregate {
+  fn parse(name: &str) -> io::Result<Aggregate> {
+    match name {
+      "product" => Ok(Aggregate::Product),
+      "sum" => Ok(Aggregate::Sum),
+      "max" => Ok(Aggregate::Max),
+      _ => Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("unknown aggregate `{}`", name),
+      )),
+    }
+  }
 
-      if self.is_gear(row + 1, c) {
-        let idx = ((row + 1) as u32) * (self.ncol as u32) + (c as u32);
-        gears.push(idx);
-      }
+  // None when the result overflows u64
+  fn apply(&self, values: impl Iterator<Item = u32>) -> Option<u64> {
+    let mut values = values.map(|v| v as u64);
+    match self {
+      Aggregate::Product => values.try_fold(1_u64, |acc, v| acc.checked_mul(v)),
+      Aggregate::Sum => values.try_fold(0_u64, |acc, v| acc.checked_add(v)),
+      Aggregate::Max => Some(values.max().unwrap_or(0)),
     }
+  }
+}
+
+// a gear is a symbol from `symbols` with exactly `adjacency` adjacent
+// numbers, at least one, its ratio is those numbers combined with `aggregate`
+struct GearRule {
+  symbols: Vec<char>,
+  adjacency: usize,
+  aggregate: Aggregate,
+}
+
+impl Default for GearRule {
+  fn default() -> GearRule {
+    GearRule {
+      symbols: vec!['*'],
+      adjacency: 2,
+      aggregate: Aggregate::Product,
+    }
+  }
+}
 
-    gears
+impl GearRule {
+  fn is_candidate(&self, symbol: &Symbol) -> bool {
+    self.symbols.contains(&symbol.value)
+  }
+
+  fn ratio(&self, schematic: &Schematic, symbol_idx: usize) -> io::Result<Option<u64>> {
+    let symbol = &schematic.symbols[symbol_idx];
+    if !self.is_candidate(symbol) || schematic.symbol_to_numbers[symbol_idx].len() != self.adjacency
+    {
+      return Ok(None);
+    }
+    let values = schematic.numbers_of_symbol(symbol_idx).map(|n| n.value);
+    match self.aggregate.apply(values) {
+      Some(ratio) => Ok(Some(ratio)),
+      None => Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+          "ratio of {} at ({}, {}) overflows u64",
+          symbol.value, symbol.row, symbol.col
+        ),
+      )),
+    }
   }
 }
 
@@ -128,93 +225,306 @@ pub fn solve() {
   println!("Part 2: {}", part2("inputs/day3/part1").unwrap());
 }
 
+pub fn run(args: &[String]) {
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("graph") => print_graph(args.get(1).map_or("inputs/day3/part1", |s| s.as_str())),
+    Some("gears") => parse_rule_args(&args[1..]).and_then(|(rule, file)| {
+      println!("Total: {}", sum_gear_ratios(&file, &rule)?);
+      Ok(())
+    }),
+    Some("render") => parse_rule_args(&args[1..]).and_then(|(rule, file)| {
+      print!("{}", render(&file, &rule)?);
+      Ok(())
+    }),
+    Some(cmd) => {
+      println!("Unknown day3 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
 fn part1(file: &str) -> io::Result<u32> {
-  let grid = Grid::build(file)?;
+  let schematic = Schematic::extract(&Grid::build(file)?)?;
   let mut total: u32 = 0;
 
-  for i in 0..grid.nrow {
-    let mut start_col: i32 = -1;
-    for j in 0..grid.ncol {
-      let is_digit = grid.is_ascii_digit(i as i32, j as i32);
-      if !is_digit || j == grid.ncol - 1 {
-        if start_col >= 0 {
-          let end_col: i32 = if j == grid.ncol - 1 && is_digit {
-            j as i32
-          } else {
-            (j as i32) - 1
-          };
-          if grid.is_adjacent_to_a_symbol(i as i32, start_col, end_col) {
-            let s = i * grid.ncol + (start_col as usize);
-            let e = i * grid.ncol + (end_col + 1) as usize;
-            let str_slice: String = grid.data[s..e].iter().collect();
-
-            if total == 0 {
-              total = str_slice.parse::<u32>().unwrap();
-            } else {
-              total += str_slice.parse::<u32>().unwrap();
-            }
-          }
-          start_col = -1;
-        }
-      } else if start_col < 0 && is_digit {
-        start_col = j as i32;
-      }
+  for (idx, number) in schematic.numbers.iter().enumerate() {
+    if schematic.symbols_of_number(idx).next().is_some() {
+      total = total.checked_add(number.value).ok_or_else(|| {
+        io::Error::new(
+          io::ErrorKind::InvalidData,
+          String::from("sum of part numbers overflows u32"),
+        )
+      })?;
     }
   }
 
   Ok(total)
 }
 
-fn part2(file: &str) -> io::Result<u32> {
+fn part2(file: &str) -> io::Result<u64> {
+  sum_gear_ratios(file, &GearRule::default())
+}
+
+fn sum_gear_ratios(file: &str, rule: &GearRule) -> io::Result<u64> {
+  let schematic = Schematic::extract(&Grid::build(file)?)?;
+  let mut sum: u64 = 0;
+
+  for idx in 0..schematic.symbols.len() {
+    if let Some(ratio) = rule.ratio(&schematic, idx)? {
+      sum = sum.checked_add(ratio).ok_or_else(|| {
+        io::Error::new(
+          io::ErrorKind::InvalidData,
+          String::from("sum of gear ratios overflows u64"),
+        )
+      })?;
+    }
+  }
+
+  Ok(sum)
+}
+
+// [--symbols *] [--count 2] [--aggregate product|sum|max] [file]
+fn parse_rule_args(args: &[String]) -> io::Result<(GearRule, String)> {
+  let mut rule = GearRule::default();
+  let mut file = String::from("inputs/day3/part1");
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    let mut value = || {
+      iter.next().ok_or_else(|| {
+        io::Error::new(
+          io::ErrorKind::InvalidInput,
+          format!("missing value for {}", arg),
+        )
+      })
+    };
+    match arg.as_str() {
+      "--symbols" => rule.symbols = value()?.chars().collect(),
+      "--count" => {
+        let count = value()?;
+        rule.adjacency = count
+          .parse::<usize>()
+          .ok()
+          .filter(|&n| n > 0)
+          .ok_or_else(|| {
+            io::Error::new(
+              io::ErrorKind::InvalidInput,
+              format!("expected a positive count, found `{}`", count),
+            )
+          })?
+      }
+      "--aggregate" => rule.aggregate = Aggregate::parse(value()?)?,
+      _ => file = arg.clone(),
+    }
+  }
+
+  Ok((rule, file))
+}
+
+// gear candidates are shown bold green when counted and bold red when their
+// adjacency does not match, numbers are green when they take part in a
+// counted gear and red when they only touch excluded ones
+fn render(file: &str, rule: &GearRule) -> io::Result<String> {
+  const GREEN: &str = "\x1b[32m";
+  const RED: &str = "\x1b[31m";
+  const BOLD_GREEN: &str = "\x1b[1;42;30m";
+  const BOLD_RED: &str = "\x1b[1;41;30m";
+  const RESET: &str = "\x1b[0m";
+
   let grid = Grid::build(file)?;
-  let mut gear_counter: HashMap<u32, Vec<u32>> = HashMap::new(); // mapping from gear index to list of adj numbers
-
-  for i in 0..grid.nrow {
-    let mut start_col: i32 = -1;
-    for j in 0..grid.ncol {
-      let is_digit = grid.is_ascii_digit(i as i32, j as i32);
-      if !is_digit || j == grid.ncol - 1 {
-        if start_col >= 0 {
-          let end_col: i32 = if j == grid.ncol - 1 && is_digit {
-            j as i32
-          } else {
-            (j as i32) - 1
-          };
-          let s = i * grid.ncol + (start_col as usize);
-          let e = i * grid.ncol + (end_col + 1) as usize;
-          let str_slice: String = grid.data[s..e].iter().collect();
-          let number = str_slice.parse::<u32>().unwrap();
-          let gears = grid.get_adjacent_gears(i as i32, start_col, end_col);
-
-          for gear in gears {
-            if gear_counter.contains_key(&gear) {
-              let adj = gear_counter.get_mut(&gear).unwrap();
-              adj.push(number);
-            } else {
-              let mut adj: Vec<u32> = Vec::new();
-              adj.push(number);
-              gear_counter.insert(gear, adj);
-            }
-          }
-          start_col = -1;
+  let schematic = Schematic::extract(&grid)?;
+  let mut styles: Vec<Option<&str>> = vec![None; grid.data.len()];
+  let mut number_styles: Vec<Option<&str>> = vec![None; schematic.numbers.len()];
+
+  let mut counted = 0;
+  let mut excluded = 0;
+  for (idx, symbol) in schematic.symbols.iter().enumerate() {
+    if !rule.is_candidate(symbol) {
+      continue;
+    }
+    let (style, number_style) = if rule.ratio(&schematic, idx)?.is_some() {
+      counted += 1;
+      (BOLD_GREEN, GREEN)
+    } else {
+      excluded += 1;
+      (BOLD_RED, RED)
+    };
+    styles[symbol.row * grid.ncol + symbol.col] = Some(style);
+    // counting in one gear wins over being left out of another
+    for &number_idx in &schematic.symbol_to_numbers[idx] {
+      if number_styles[number_idx] != Some(GREEN) {
+        number_styles[number_idx] = Some(number_style);
+      }
+    }
+  }
+
+  for (number, style) in schematic.numbers.iter().zip(&number_styles) {
+    for col in number.start_col..=number.end_col {
+      styles[number.row * grid.ncol + col] = *style;
+    }
+  }
+
+  let mut output = String::new();
+  for row in 0..grid.nrow {
+    for col in 0..grid.ncol {
+      match styles[row * grid.ncol + col] {
+        Some(style) => {
+          output.push_str(style);
+          output.push(grid.get(row, col));
+          output.push_str(RESET);
         }
-      } else if start_col < 0 && is_digit {
-        start_col = j as i32;
+        None => output.push(grid.get(row, col)),
       }
     }
+    output.push('\n');
   }
 
-  let filtered_gear_counter: HashMap<u32, Vec<u32>> = gear_counter
-    .into_iter()
-    .filter(|(_, adj)| adj.len() == 2)
+  let count_numbers = |style: &str| number_styles.iter().filter(|&&s| s == Some(style)).count();
+  output.push_str(&format!(
+    "{}gears: {}{}, {}excluded gears: {}{}, {}gear numbers: {}{}, {}excluded numbers: {}{}\n",
+    BOLD_GREEN,
+    counted,
+    RESET,
+    BOLD_RED,
+    excluded,
+    RESET,
+    GREEN,
+    count_numbers(GREEN),
+    RESET,
+    RED,
+    count_numbers(RED),
+    RESET
+  ));
+
+  Ok(output)
+}
+
+fn print_graph(file: &str) -> io::Result<()> {
+  let schematic = Schematic::extract(&Grid::build(file)?)?;
+
+  for (idx, symbol) in schematic.symbols.iter().enumerate() {
+    let adj: Vec<u32> = schematic.numbers_of_symbol(idx).map(|n| n.value).collect();
+    println!(
+      "{} at ({}, {}): {:?}",
+      symbol.value, symbol.row, symbol.col, adj
+    );
+  }
+
+  let orphans: Vec<String> = schematic
+    .orphan_numbers()
+    .map(|n| {
+      format!(
+        "{} at ({}, {}..={})",
+        n.value, n.row, n.start_col, n.end_col
+      )
+    })
     .collect();
+  println!(
+    "{} numbers, {} symbols, {} orphan numbers",
+    schematic.numbers.len(),
+    schematic.symbols.len(),
+    orphans.len()
+  );
+  for orphan in orphans {
+    println!("  {}", orphan);
+  }
 
-  let mut sum: u32 = 0;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-  for (_, adj) in &filtered_gear_counter {
-    let multiplication: u32 = adj.iter().product();
-    sum += multiplication;
+  fn grid(rows: &[&str]) -> Grid {
+    Grid {
+      data: rows.iter().flat_map(|row| row.chars()).collect(),
+      nrow: rows.len(),
+      ncol: rows[0].len(),
+    }
   }
 
-  Ok(sum)
+  #[test]
+  fn test_adjacency_on_sample() {
+    let schematic = Schematic::extract(&Grid::build("inputs/day3/sample1").unwrap()).unwrap();
+    let values = |numbers: Vec<&NumberToken>| numbers.iter().map(|n| n.value).collect::<Vec<u32>>();
+    assert_eq!(schematic.numbers.len(), 10);
+    assert_eq!(schematic.symbols.len(), 6);
+
+    // the first `*` touches 467 and 35, the one below it only 617
+    assert_eq!(
+      values(schematic.numbers_of_symbol(0).collect()),
+      vec![467, 35]
+    );
+    assert_eq!(values(schematic.numbers_of_symbol(2).collect()), vec![617]);
+    let symbols: Vec<char> = schematic.symbols_of_number(3).map(|s| s.value).collect();
+    assert_eq!(symbols, vec!['#']);
+    assert_eq!(schematic.symbols_of_number(1).count(), 0);
+    assert_eq!(values(schematic.orphan_numbers().collect()), vec![114, 58]);
+
+    assert_eq!(part1("inputs/day3/sample1").unwrap(), 4361);
+  }
+
+  #[test]
+  fn test_numbers_must_fit_in_u32() {
+    let schematic = Schematic::extract(&grid(&["4294967295*"])).unwrap();
+    assert_eq!(schematic.numbers[0].value, u32::MAX);
+    assert!(Schematic::extract(&grid(&["4294967296*"])).is_err());
+  }
+
+  #[test]
+  fn test_gear_rules_on_sample() {
+    let sample = "inputs/day3/sample1";
+    let rule = |symbols: &str, adjacency: usize, aggregate: Aggregate| GearRule {
+      symbols: symbols.chars().collect(),
+      adjacency,
+      aggregate,
+    };
+
+    assert_eq!(part2(sample).unwrap(), 467835);
+    assert_eq!(
+      sum_gear_ratios(sample, &rule("*", 2, Aggregate::Sum)).unwrap(),
+      467 + 35 + 755 + 598
+    );
+    assert_eq!(
+      sum_gear_ratios(sample, &rule("*", 2, Aggregate::Max)).unwrap(),
+      467 + 755
+    );
+    assert_eq!(
+      sum_gear_ratios(sample, &rule("*", 1, Aggregate::Product)).unwrap(),
+      617
+    );
+    assert_eq!(
+      sum_gear_ratios(sample, &rule("#+$*", 1, Aggregate::Sum)).unwrap(),
+      633 + 592 + 617 + 664
+    );
+    assert_eq!(
+      sum_gear_ratios(sample, &rule("*", 3, Aggregate::Product)).unwrap(),
+      0
+    );
+  }
+
+  #[test]
+  fn test_gear_ratio_overflow() {
+    let values = || [u32::MAX; 3].into_iter();
+    assert_eq!(Aggregate::Product.apply(values()), None);
+    assert_eq!(Aggregate::Sum.apply(values()), Some(3 * u32::MAX as u64));
+    assert_eq!(Aggregate::Max.apply(values()), Some(u32::MAX as u64));
+
+    let schematic =
+      Schematic::extract(&grid(&["4294967295.", "*4294967295", "4294967295."])).unwrap();
+    let rule = GearRule {
+      adjacency: 3,
+      ..GearRule::default()
+    };
+    assert!(rule.ratio(&schematic, 0).is_err());
+  }
 }
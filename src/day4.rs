@@ -1,7 +1,11 @@
+use crate::bench;
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::{Rng, SeedableRng};
 use std::cmp;
 use std::collections::HashSet;
-use std::fs;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
 
 #[derive(Clone)]
 struct Card {
@@ -9,26 +13,365 @@ struct Card {
   matching_count: u32,
 }
 
+// both sides of a card share one representation: a bitset of 64 bit words
+// sized to the largest number, so counting matches is an `and` plus
+// `count_ones` per word, or sorted lists when the numbers are too large
+#[derive(Clone)]
+enum NumberSet {
+  Bits(Vec<u64>),
+  Sorted(Vec<u32>),
+}
+
+impl NumberSet {
+  // numbers up to 64 * 64 - 1 use the bitset
+  const MAX_WORDS: usize = 64;
+
+  fn parse_numbers(text: &str) -> io::Result<Vec<u32>> {
+    text
+      .split_whitespace()
+      .map(|number| {
+        number
+          .parse::<u32>()
+          .map_err(|_| invalid_data(format!("malformed number `{}`", number)))
+      })
+      .collect()
+  }
+
+  // `words` must cover the largest number
+  fn build(mut numbers: Vec<u32>, words: usize) -> io::Result<NumberSet> {
+    let duplicate = |number: u32| invalid_data(format!("duplicate number {}", number));
+    if words > NumberSet::MAX_WORDS {
+      numbers.sort_unstable();
+      return match numbers.windows(2).find(|pair| pair[0] == pair[1]) {
+        Some(pair) => Err(duplicate(pair[0])),
+        None => Ok(NumberSet::Sorted(numbers)),
+      };
+    }
+    let mut bits: Vec<u64> = vec![0; words];
+    for number in numbers {
+      let (word, bit) = (number as usize / 64, 1 << (number % 64));
+      if bits[word] & bit != 0 {
+        return Err(duplicate(number));
+      }
+      bits[word] |= bit;
+    }
+    Ok(NumberSet::Bits(bits))
+  }
+
+  fn count_common(&self, other: &NumberSet) -> u32 {
+    match (self, other) {
+      (NumberSet::Bits(a), NumberSet::Bits(b)) => {
+        a.iter().zip(b).map(|(a, b)| (a & b).count_ones()).sum()
+      }
+      (NumberSet::Sorted(a), NumberSet::Sorted(b)) => {
+        a.iter().filter(|n| b.binary_search(n).is_ok()).count() as u32
+      }
+      _ => unreachable!("both sides of a card share a representation"),
+    }
+  }
+}
+
+struct Scratchcard {
+  id: u32,
+  winning: NumberSet,
+  have: NumberSet,
+}
+
+impl Scratchcard {
+  fn parse(line: &str) -> io::Result<Scratchcard> {
+    let (header, numbers) = line
+      .split_once(':')
+      .ok_or_else(|| invalid_data(format!("missing `:` in `{}`", line)))?;
+    let id = header
+      .strip_prefix("Card")
+      .and_then(|id| id.trim().parse::<u32>().ok())
+      .ok_or_else(|| invalid_data(format!("malformed card header `{}`", header)))?;
+    let (winning, have) = numbers
+      .split_once('|')
+      .ok_or_else(|| invalid_data(format!("missing `|` in `{}`", line)))?;
+
+    let (winning, have) = (
+      NumberSet::parse_numbers(winning)?,
+      NumberSet::parse_numbers(have)?,
+    );
+    let largest = winning.iter().chain(&have).copied().max().unwrap_or(0);
+    let words = largest as usize / 64 + 1;
+
+    Ok(Scratchcard {
+      id,
+      winning: NumberSet::build(winning, words)?,
+      have: NumberSet::build(have, words)?,
+    })
+  }
+
+  fn matches(&self) -> u32 {
+    self.winning.count_common(&self.have)
+  }
+}
+
+// how many of the following cards a card wins copies of
+#[derive(Clone, Copy)]
+enum WinRule {
+  Matches,
+  Capped(u64),
+  Scaled(u64),
+}
+
+// how many points a card with `matches` winning numbers scores
+#[derive(Clone, Copy)]
+enum PointRule {
+  Doubling,
+  Linear,
+  Capped(u64),
+  Multiplier(u64),
+}
+
+impl WinRule {
+  fn parse(text: &str) -> io::Result<WinRule> {
+    match parse_rule(text)? {
+      ("matches", None) => Ok(WinRule::Matches),
+      ("capped", Some(n)) => Ok(WinRule::Capped(n)),
+      ("scaled", Some(n)) => Ok(WinRule::Scaled(n)),
+      _ => Err(invalid_input(format!("unknown win rule `{}`", text))),
+    }
+  }
+
+  // None when the count overflows u64
+  fn cards_won(&self, matches: u32) -> Option<u64> {
+    let matches = matches as u64;
+    match self {
+      WinRule::Matches => Some(matches),
+      WinRule::Capped(cap) => Some(matches.min(*cap)),
+      WinRule::Scaled(factor) => matches.checked_mul(*factor),
+    }
+  }
+}
+
+impl PointRule {
+  fn parse(text: &str) -> io::Result<PointRule> {
+    match parse_rule(text)? {
+      ("doubling", None) => Ok(PointRule::Doubling),
+      ("linear", None) => Ok(PointRule::Linear),
+      ("capped", Some(n)) => Ok(PointRule::Capped(n)),
+      ("multiplier", Some(n)) => Ok(PointRule::Multiplier(n)),
+      _ => Err(invalid_input(format!("unknown point rule `{}`", text))),
+    }
+  }
+
+  // None when the points overflow u64
+  fn points(&self, matches: u32) -> Option<u64> {
+    if matches == 0 {
+      return Some(0);
+    }
+    match self {
+      PointRule::Doubling => 1_u64.checked_shl(matches - 1),
+      PointRule::Linear => Some(matches as u64),
+      // past 64 matches the doubled value is over any u64 cap
+      PointRule::Capped(cap) => Some(1_u64.checked_shl(matches - 1).map_or(*cap, |p| p.min(*cap))),
+      PointRule::Multiplier(base) => base.checked_pow(matches - 1),
+    }
+  }
+}
+
+// `name` or `name:N`
+fn parse_rule(text: &str) -> io::Result<(&str, Option<u64>)> {
+  match text.split_once(':') {
+    None => Ok((text, None)),
+    Some((name, n)) => {
+      let n = n
+        .parse::<u64>()
+        .map_err(|_| invalid_input(format!("malformed rule argument `{}`", text)))?;
+      Ok((name, Some(n)))
+    }
+  }
+}
+
+struct CascadeReport {
+  copies: Vec<u64>,
+  points: u64,
+  // card ids of the longest sequence where each card won a copy of the next
+  longest_chain: Vec<u32>,
+  // card id and the number of copies its wins added
+  top_contributor: Option<(u32, u64)>,
+}
+
+fn cascade(cards: &[Scratchcard], wins: WinRule, points: PointRule) -> io::Result<CascadeReport> {
+  let mut copies: Vec<u64> = vec![1; cards.len()];
+  let mut total_points: u64 = 0;
+  let mut chain_len: Vec<usize> = vec![1; cards.len()];
+  let mut chain_prev: Vec<Option<usize>> = vec![None; cards.len()];
+  let mut top_contributor: Option<(u32, u64)> = None;
+
+  for (idx, card) in cards.iter().enumerate() {
+    let matches = card.matches();
+    let overflow = |what: &str| invalid_data(format!("{} of card {} overflow", what, card.id));
+    total_points = points
+      .points(matches)
+      .and_then(|card_points| total_points.checked_add(card_points))
+      .ok_or_else(|| overflow("points"))?;
+
+    // wins past the last card are dropped, so a count beyond usize is as
+    // good as usize::MAX
+    let won = wins
+      .cards_won(matches)
+      .ok_or_else(|| overflow("cards won"))?;
+    let won = usize::try_from(won).unwrap_or(usize::MAX);
+    let last = cmp::min(cards.len(), (idx + 1).saturating_add(won));
+    for idx2 in idx + 1..last {
+      copies[idx2] = copies[idx2]
+        .checked_add(copies[idx])
+        .ok_or_else(|| invalid_data(format!("copies of card {} overflow", cards[idx2].id)))?;
+      if chain_len[idx] + 1 > chain_len[idx2] {
+        chain_len[idx2] = chain_len[idx] + 1;
+        chain_prev[idx2] = Some(idx);
+      }
+    }
+
+    let contributed = copies[idx]
+      .checked_mul((last - idx - 1) as u64)
+      .ok_or_else(|| overflow("copies won"))?;
+    if contributed > 0 && top_contributor.is_none_or(|(_, best)| contributed > best) {
+      top_contributor = Some((card.id, contributed));
+    }
+  }
+
+  let mut longest_chain: Vec<u32> = Vec::new();
+  let mut current = (0..cards.len()).max_by_key(|&idx| (chain_len[idx], cmp::Reverse(idx)));
+  while let Some(idx) = current {
+    longest_chain.push(cards[idx].id);
+    current = chain_prev[idx];
+  }
+  longest_chain.reverse();
+
+  Ok(CascadeReport {
+    copies,
+    points: total_points,
+    longest_chain,
+    top_contributor,
+  })
+}
+
 pub fn solve() {
   println!("Part 1: {}", part1("inputs/day4/part1").unwrap());
   println!("Part 2: {}", part2("inputs/day4/part1").unwrap());
 }
 
-fn part1(file: &str) -> io::Result<u32> {
+pub fn run(args: &[String]) {
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("bench") => match args.get(1).map(|s| s.parse::<usize>()) {
+      None => bench(500_000),
+      Some(Ok(cards)) => bench(cards),
+      Some(Err(_)) => Err(invalid_input(format!("malformed card count `{}`", args[1]))),
+    },
+    Some("cascade") => print_cascade(&args[1..]),
+    Some(cmd) => {
+      println!("Unknown day4 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
+fn part1(file: &str) -> io::Result<u64> {
+  let cards = parse_cards(file)?;
+  Ok(cascade(&cards, WinRule::Matches, PointRule::Doubling)?.points)
+}
+
+fn part2(file: &str) -> io::Result<u64> {
+  let cards = parse_cards(file)?;
+  let report = cascade(&cards, WinRule::Matches, PointRule::Doubling)?;
+  Ok(report.copies.iter().sum())
+}
+
+// [--wins matches|capped:N|scaled:N]
+// [--points doubling|linear|capped:N|multiplier:N] [file]
+fn print_cascade(args: &[String]) -> io::Result<()> {
+  let mut wins = WinRule::Matches;
+  let mut points = PointRule::Doubling;
+  let mut file = "inputs/day4/part1";
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--wins" => wins = WinRule::parse(iter.next().map_or("", |s| s.as_str()))?,
+      "--points" => points = PointRule::parse(iter.next().map_or("", |s| s.as_str()))?,
+      _ => file = arg,
+    }
+  }
+
+  let cards = parse_cards(file)?;
+  let report = cascade(&cards, wins, points)?;
+
+  for (card, copies) in cards.iter().zip(report.copies.iter()) {
+    println!("Card {}: {} copies", card.id, copies);
+  }
+  println!("Points: {}", report.points);
+  println!("Total cards: {}", report.copies.iter().sum::<u64>());
+  println!(
+    "Longest chain ({} cards): {:?}",
+    report.longest_chain.len(),
+    report.longest_chain
+  );
+  match report.top_contributor {
+    Some((id, copies)) => println!("Top contributor: card {} with {} copies", id, copies),
+    None => println!("Top contributor: none, no card won any copies"),
+  }
+
+  Ok(())
+}
+
+// cards must be numbered 1, 2, 3, ... since part 2 wins copies by position
+fn parse_cards(file: &str) -> io::Result<Vec<Scratchcard>> {
+  let input = fs::read_to_string(file)?;
+  let mut cards: Vec<Scratchcard> = Vec::new();
+
+  for (idx, line) in input.lines().enumerate() {
+    let card =
+      Scratchcard::parse(line).map_err(|err| invalid_data(format!("line {}: {}", idx + 1, err)))?;
+    if card.id != idx as u32 + 1 {
+      return Err(invalid_data(format!(
+        "line {}: expected card {} but found card {}",
+        idx + 1,
+        idx + 1,
+        card.id
+      )));
+    }
+    cards.push(card);
+  }
+
+  Ok(cards)
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn part1_hashset(file: &str) -> io::Result<u32> {
   let input = fs::read_to_string(file)?;
   let mut total: u32 = 0;
   for line in input.lines() {
     let (winning_numbers, numbers_i_have) = get_numbers(line);
     let my_winning_numbers = winning_numbers.intersection(&numbers_i_have).count() as u32;
     if my_winning_numbers > 0 {
-      total += (2 as u32).pow(my_winning_numbers - 1);
+      total += 2_u32.pow(my_winning_numbers - 1);
     }
   }
 
   Ok(total)
 }
 
-fn part2(file: &str) -> io::Result<u32> {
+fn part2_hashset(file: &str) -> io::Result<u32> {
   let input = fs::read_to_string(file)?;
   let line_count = input.lines().count();
   let mut cards = vec![
@@ -67,17 +410,152 @@ fn get_numbers(line: &str) -> (HashSet<u32>, HashSet<u32>) {
 
   // get winning numbers
   let mut winning_numbers = HashSet::new();
-  let mut split = winning_numbers_line.split_whitespace();
-  while let Some(number) = split.next() {
+  for number in winning_numbers_line.split_whitespace() {
     winning_numbers.insert(number.parse::<u32>().unwrap());
   }
 
   // get numbers i have
   let mut numbers_i_have = HashSet::new();
-  let mut split = numbers_i_have_line.split_whitespace();
-  while let Some(number) = split.next() {
+  for number in numbers_i_have_line.split_whitespace() {
     numbers_i_have.insert(number.parse::<u32>().unwrap());
   }
 
   (winning_numbers, numbers_i_have)
 }
+
+fn bench(cards: usize) -> io::Result<()> {
+  bench::compare(
+    "day4",
+    cards,
+    "cards",
+    generate_input,
+    ["hashset", "bitset"],
+    &[
+      ("Part 1", |file| part1_hashset(file).map(u64::from), part1),
+      ("Part 2", |file| part2_hashset(file).map(u64::from), part2),
+    ],
+  )
+}
+
+fn generate_input(file: &str, cards: usize) -> io::Result<()> {
+  // mostly losing cards so the copy counts stay within the legacy u32 total
+  let mut rng = StdRng::seed_from_u64(2023);
+  let mut writer = BufWriter::new(File::create(file)?);
+
+  for id in 1..=cards {
+    let matches = if rng.gen_ratio(7, 10) {
+      0
+    } else {
+      rng.gen_range(1..=4)
+    };
+    let numbers = index::sample(&mut rng, 100, 35 - matches);
+    let winning: Vec<String> = numbers
+      .iter()
+      .take(10)
+      .map(|n| format!("{:2}", n))
+      .collect();
+    let have: Vec<String> = numbers
+      .iter()
+      .skip(10 - matches)
+      .map(|n| format!("{:2}", n))
+      .collect();
+    writeln!(
+      writer,
+      "Card {}: {} | {}",
+      id,
+      winning.join(" "),
+      have.join(" ")
+    )?;
+  }
+
+  writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn test_number_sets() {
+    let card = Scratchcard::parse("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53").unwrap();
+    assert_eq!(card.matches(), 4);
+
+    // past 127 the bitset grows, past 64 * 64 - 1 the sides are sorted lists
+    let card = Scratchcard::parse("Card 1: 1 200 4095 | 4095 200 7").unwrap();
+    assert!(matches!(card.winning, NumberSet::Bits(ref bits) if bits.len() == 64));
+    assert_eq!(card.matches(), 2);
+    let card = Scratchcard::parse("Card 1: 1 4096 3000000000 | 3000000000 4096 7").unwrap();
+    assert!(matches!(card.winning, NumberSet::Sorted(_)));
+    assert_eq!(card.matches(), 2);
+
+    assert!(Scratchcard::parse("Card 1: 5 17 5 | 1 2").is_err());
+    assert!(Scratchcard::parse("Card 1: 1 2 | 9000 3 9000").is_err());
+    assert!(Scratchcard::parse("Card 1: 1 2 | 4294967296").is_err());
+    assert!(Scratchcard::parse("Card 1: 1 x | 2").is_err());
+  }
+
+  #[test]
+  fn test_parse_cards_checks_ids() {
+    let path = env::temp_dir().join("aoc2023-day4-test-ids");
+    let file = path.to_str().unwrap();
+    fs::write(file, "Card 1: 1 | 1\nCard 2: 2 | 3\n").unwrap();
+    let cards = parse_cards(file).map(|cards| cards.len());
+    fs::write(file, "Card 1: 1 | 1\nCard 3: 2 | 3\n").unwrap();
+    let skipped = parse_cards(file).map(|cards| cards.len());
+    fs::remove_file(file).unwrap();
+
+    assert_eq!(cards.unwrap(), 2);
+    assert_eq!(
+      skipped.unwrap_err().to_string(),
+      "line 2: expected card 2 but found card 3"
+    );
+  }
+
+  #[test]
+  fn test_win_rules() {
+    assert_eq!(WinRule::Matches.cards_won(4), Some(4));
+    assert_eq!(WinRule::Capped(2).cards_won(4), Some(2));
+    assert_eq!(WinRule::Capped(2).cards_won(1), Some(1));
+    assert_eq!(WinRule::Scaled(3).cards_won(4), Some(12));
+    assert_eq!(WinRule::Scaled(u64::MAX).cards_won(2), None);
+    assert!(WinRule::parse("scaled").is_err());
+    assert!(WinRule::parse("capped:x").is_err());
+  }
+
+  #[test]
+  fn test_point_rules() {
+    assert_eq!(PointRule::Doubling.points(0), Some(0));
+    assert_eq!(PointRule::Doubling.points(4), Some(8));
+    assert_eq!(PointRule::Doubling.points(65), None);
+    assert_eq!(PointRule::Linear.points(4), Some(4));
+    assert_eq!(PointRule::Capped(5).points(3), Some(4));
+    assert_eq!(PointRule::Capped(5).points(4), Some(5));
+    assert_eq!(PointRule::Capped(5).points(100), Some(5));
+    assert_eq!(PointRule::Multiplier(3).points(4), Some(27));
+    assert_eq!(PointRule::Multiplier(u64::MAX).points(3), None);
+  }
+
+  #[test]
+  fn test_cascade_on_sample() {
+    let cards = parse_cards("inputs/day4/sample1").unwrap();
+    let report = cascade(&cards, WinRule::Matches, PointRule::Doubling).unwrap();
+    assert_eq!(report.points, 13);
+    assert_eq!(report.copies, vec![1, 2, 4, 8, 14, 1]);
+    assert_eq!(report.longest_chain, vec![1, 2, 3, 4, 5]);
+    // cards 3 and 4 both add 8 copies, the first one wins the tie
+    assert_eq!(report.top_contributor, Some((3, 8)));
+
+    let report = cascade(&cards, WinRule::Capped(1), PointRule::Linear).unwrap();
+    assert_eq!(report.points, 9);
+    assert_eq!(report.copies, vec![1, 2, 3, 4, 5, 1]);
+    assert_eq!(report.top_contributor, Some((4, 4)));
+
+    let report = cascade(&cards[4..], WinRule::Matches, PointRule::Doubling).unwrap();
+    assert_eq!(report.longest_chain, vec![5]);
+    assert_eq!(report.top_contributor, None);
+
+    let overflow = cascade(&cards, WinRule::Matches, PointRule::Multiplier(u64::MAX));
+    assert!(overflow.is_err());
+  }
+}
@@ -1,6 +1,8 @@
 use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
 use std::fs;
 use std::io;
 
@@ -12,14 +14,498 @@ struct Range {
   dst: u64,
 }
 
+// sorted, disjoint and non-adjacent half-open intervals `[start, end)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct IntervalSet {
+  intervals: Vec<(u64, u64)>,
+}
+
+impl IntervalSet {
+  fn new(mut intervals: Vec<(u64, u64)>) -> IntervalSet {
+    intervals.retain(|(start, end)| start < end);
+    intervals.sort_unstable();
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+      match merged.last_mut() {
+        Some(last) if start <= last.1 => last.1 = last.1.max(end),
+        _ => merged.push((start, end)),
+      }
+    }
+
+    IntervalSet { intervals: merged }
+  }
+
+  // seeds are given as `start len` pairs
+  fn from_seed_pairs(seeds: &[u64]) -> io::Result<IntervalSet> {
+    let intervals = seeds
+      .chunks(2)
+      .map(|chunk| match *chunk {
+        [start, len] => start
+          .checked_add(len)
+          .map(|end| (start, end))
+          .ok_or_else(|| invalid_data(format!("seed range {} + {} overflows u64", start, len))),
+        _ => Err(invalid_data(format!(
+          "{} seed numbers do not form `start len` pairs",
+          seeds.len()
+        ))),
+      })
+      .collect::<io::Result<Vec<(u64, u64)>>>()?;
+    Ok(IntervalSet::new(intervals))
+  }
+
+  // pushes every interval through the mapping, splitting it at the mapping's
+  // breakpoints, so the cost depends on the number of pieces, not values
+  fn map_through(&self, sorted_mapping: &[Range]) -> IntervalSet {
+    let mut output: Vec<(u64, u64)> = Vec::new();
+
+    for &(start, end) in &self.intervals {
+      let mut pos = start;
+      // index of the first breakpoint after `pos`, the piece containing
+      // `pos` starts at the breakpoint before it
+      let mut idx = sorted_mapping.partition_point(|range| range.src <= pos);
+      while pos < end {
+        let piece_end = match sorted_mapping.get(idx) {
+          Some(next) => next.src.min(end),
+          None => end,
+        };
+        // before the first and from the last breakpoint on values map to
+        // themselves, same as `get_output`
+        let mapped = if idx == 0 || idx == sorted_mapping.len() {
+          pos
+        } else {
+          let range = &sorted_mapping[idx - 1];
+          range.dst + (pos - range.src)
+        };
+        output.push((mapped, mapped + (piece_end - pos)));
+        pos = piece_end;
+        idx += 1;
+      }
+    }
+
+    IntervalSet::new(output)
+  }
+
+  fn min(&self) -> Option<u64> {
+    self.intervals.first().map(|(start, _)| *start)
+  }
+
+  fn count(&self) -> u64 {
+    self.intervals.iter().map(|(start, end)| end - start).sum()
+  }
+
+  fn intersect(&self, other: &IntervalSet) -> IntervalSet {
+    let mut output: Vec<(u64, u64)> = Vec::new();
+    for &(start, end) in &self.intervals {
+      output.extend(other.overlaps(start, end));
+    }
+    IntervalSet::new(output)
+  }
+
+  // parts of the set that fall inside `[start, end)`
+  fn overlaps(&self, start: u64, end: u64) -> impl Iterator<Item = (u64, u64)> + '_ {
+    let first = self.intervals.partition_point(|&(_, e)| e <= start);
+    self.intervals[first..]
+      .iter()
+      .take_while(move |&&(s, _)| s < end)
+      .map(move |&(s, e)| (s.max(start), e.min(end)))
+  }
+
+  // parts of the set outside every interval of `other`
+  fn difference(&self, other: &IntervalSet) -> IntervalSet {
+    let mut output: Vec<(u64, u64)> = Vec::new();
+    for &(start, end) in &self.intervals {
+      let mut pos = start;
+      for (s, e) in other.overlaps(start, end) {
+        output.push((pos, s));
+        pos = e;
+      }
+      output.push((pos, end));
+    }
+    IntervalSet::new(output)
+  }
+
+  // every value whose image through the mapping lands in this set
+  fn preimage_through(&self, sorted_mapping: &[Range]) -> IntervalSet {
+    let mut values: Vec<(u64, u64)> = Vec::new();
+
+    for (src, end, dst) in segments(sorted_mapping) {
+      let image_end = dst.saturating_add(end - src);
+      for (start, stop) in self.overlaps(dst, image_end) {
+        values.push((src + (start - dst), src + (stop - dst)));
+      }
+    }
+
+    IntervalSet::new(values)
+  }
+}
+
+// splits the whole u64 line into `(src, end, dst)` pieces of the mapping,
+// where `[src, end)` maps onto `[dst, dst + end - src)`
+fn segments(sorted_mapping: &[Range]) -> Vec<(u64, u64, u64)> {
+  let mut segments: Vec<(u64, u64, u64)> = Vec::with_capacity(sorted_mapping.len() + 1);
+  let first = sorted_mapping.first().map_or(u64::MAX, |range| range.src);
+  segments.push((0, first, 0));
+  for pair in sorted_mapping.windows(2) {
+    segments.push((pair[0].src, pair[1].src, pair[0].dst));
+  }
+  if let Some(last) = sorted_mapping.last() {
+    segments.push((last.src, u64::MAX, last.src));
+  }
+  segments.retain(|(src, end, _)| src < end);
+  segments
+}
+
+struct MapEntry {
+  dst: u64,
+  src: u64,
+  len: u64,
+  line: usize,
+}
+
+impl MapEntry {
+  fn src_end(&self) -> io::Result<u64> {
+    self.src.checked_add(self.len).ok_or_else(|| {
+      invalid_data(format!(
+        "line {}: source range {} + {} overflows u64",
+        self.line, self.src, self.len
+      ))
+    })
+  }
+}
+
+// one `source-to-target map:` block of the almanac
+struct CategoryMap {
+  source: String,
+  target: String,
+  line: usize,
+  entries: Vec<MapEntry>,
+}
+
+impl CategoryMap {
+  // source values some entry of the map covers, the rest pass through
+  fn covered(&self) -> io::Result<IntervalSet> {
+    let mut intervals: Vec<(u64, u64)> = Vec::new();
+    for entry in &self.entries {
+      intervals.push((entry.src, entry.src_end()?));
+    }
+    Ok(IntervalSet::new(intervals))
+  }
+
+  fn to_ranges(&self) -> io::Result<Vec<Range>> {
+    let mut src_to_dst: HashMap<u64, u64> = HashMap::new();
+    for entry in &self.entries {
+      src_to_dst.insert(entry.src, entry.dst);
+      // the end of a range passes through unless another range starts there
+      src_to_dst.entry(entry.src_end()?).or_insert(EMPTY);
+    }
+
+    let mut ranges = src_to_dst
+      .iter()
+      .map(|(src, dst)| Range {
+        src: *src,
+        dst: if *dst == EMPTY { *src } else { *dst },
+      })
+      .collect::<Vec<Range>>();
+    ranges.sort_by_key(|range| range.src);
+    Ok(ranges)
+  }
+}
+
+#[derive(Debug)]
+enum Severity {
+  Error,
+  Warning,
+}
+
+struct Diagnostic {
+  severity: Severity,
+  line: usize,
+  message: String,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{:?}: line {}: {}",
+      self.severity, self.line, self.message
+    )
+  }
+}
+
+struct Almanac {
+  seeds: Vec<u64>,
+  maps: Vec<CategoryMap>,
+}
+
+impl Almanac {
+  fn parse(file: &str) -> io::Result<Almanac> {
+    let input = fs::read_to_string(file)?;
+    let mut seeds: Vec<u64> = Vec::new();
+    let mut maps: Vec<CategoryMap> = Vec::new();
+
+    for (idx, line) in input.lines().enumerate() {
+      let line_no = idx + 1;
+      let error = |message: String| invalid_data(format!("line {}: {}", line_no, message));
+      let parse_numbers = |text: &str| {
+        text
+          .split_whitespace()
+          .map(|s| s.parse::<u64>())
+          .collect::<Result<Vec<u64>, _>>()
+          .map_err(|_| error(format!("malformed numbers `{}`", text)))
+      };
+
+      if let Some(numbers) = line.strip_prefix("seeds:") {
+        seeds = parse_numbers(numbers)?;
+      } else if let Some(header) = line.strip_suffix(" map:") {
+        let (source, target) = header
+          .split_once("-to-")
+          .ok_or_else(|| error(format!("malformed map header `{}`", line)))?;
+        maps.push(CategoryMap {
+          source: source.to_string(),
+          target: target.to_string(),
+          line: line_no,
+          entries: Vec::new(),
+        });
+      } else if !line.trim().is_empty() {
+        let numbers = parse_numbers(line)?;
+        let map = maps
+          .last_mut()
+          .ok_or_else(|| error(String::from("range outside of any map")))?;
+        match numbers[..] {
+          [dst, src, len] => map.entries.push(MapEntry {
+            dst,
+            src,
+            len,
+            line: line_no,
+          }),
+          _ => return Err(error(format!("expected 3 numbers, found `{}`", line))),
+        }
+      }
+    }
+
+    Ok(Almanac { seeds, maps })
+  }
+
+  // shortest chain of maps leading from one category to another
+  fn path(&self, from: &str, to: &str) -> io::Result<Vec<&CategoryMap>> {
+    let mut prev: HashMap<&str, usize> = HashMap::new();
+    let mut queue: VecDeque<&str> = VecDeque::from([from]);
+
+    while let Some(category) = queue.pop_front() {
+      if category == to {
+        let mut path: Vec<&CategoryMap> = Vec::new();
+        let mut current = to;
+        while current != from {
+          let map = &self.maps[prev[current]];
+          path.push(map);
+          current = &map.source;
+        }
+        path.reverse();
+        return Ok(path);
+      }
+      for (idx, map) in self.maps.iter().enumerate() {
+        if map.source == category && map.target != from && !prev.contains_key(map.target.as_str()) {
+          prev.insert(&map.target, idx);
+          queue.push_back(&map.target);
+        }
+      }
+    }
+
+    Err(invalid_data(format!(
+      "no maps lead from {} to {}",
+      from, to
+    )))
+  }
+
+  fn compose(&self, from: &str, to: &str) -> io::Result<Vec<Range>> {
+    let mut mapping: Vec<Range> = Vec::new();
+    for map in self.path(from, to)? {
+      mapping = merge(&mapping, &map.to_ranges()?);
+    }
+    Ok(mapping)
+  }
+
+  // the values that no map on the way covers, a value keeps itself only
+  // while it stays uncovered, so these pass through unchanged
+  fn unmapped(&self, from: &str, to: &str, values: &IntervalSet) -> io::Result<IntervalSet> {
+    let mut unmapped = values.clone();
+    for map in self.path(from, to)? {
+      unmapped = unmapped.difference(&map.covered()?);
+    }
+    Ok(unmapped)
+  }
+
+  fn validate(&self) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    for (idx, map) in self.maps.iter().enumerate() {
+      if let Some(other) = self.maps[..idx]
+        .iter()
+        .find(|other| other.source == map.source && other.target == map.target)
+      {
+        diagnostics.push(Diagnostic {
+          severity: Severity::Error,
+          line: map.line,
+          message: format!(
+            "{}-to-{} map is already defined on line {}",
+            map.source, map.target, other.line
+          ),
+        });
+      }
+
+      let mut entries: Vec<&MapEntry> = map.entries.iter().collect();
+      entries.sort_by_key(|entry| entry.src);
+      // the entry reaching furthest so far, an entry starting before its end
+      // overlaps it and one starting after it leaves a gap
+      let mut reach: Option<(u64, &MapEntry)> = None;
+      for entry in entries {
+        let end = match entry.src_end() {
+          Ok(end) => end,
+          Err(_) => {
+            diagnostics.push(Diagnostic {
+              severity: Severity::Error,
+              line: entry.line,
+              message: format!(
+                "{}-to-{} source range {} + {} overflows u64",
+                map.source, map.target, entry.src, entry.len
+              ),
+            });
+            continue;
+          }
+        };
+        if let Some((prev_end, prev)) = reach {
+          if entry.src < prev_end {
+            diagnostics.push(Diagnostic {
+              severity: Severity::Error,
+              line: entry.line,
+              message: format!(
+                "{}-to-{} source range [{}, {}) overlaps [{}, {}) from line {}",
+                map.source, map.target, entry.src, end, prev.src, prev_end, prev.line
+              ),
+            });
+          } else if prev_end < entry.src {
+            diagnostics.push(Diagnostic {
+              severity: Severity::Warning,
+              line: entry.line,
+              message: format!(
+                "{}-to-{} leaves a gap [{}, {}) that passes through unmapped",
+                map.source, map.target, prev_end, entry.src
+              ),
+            });
+          }
+        }
+        if reach.is_none_or(|(prev_end, _)| end > prev_end) {
+          reach = Some((end, entry));
+        }
+      }
+    }
+
+    if let Some(cycle) = self.find_cycle() {
+      diagnostics.push(Diagnostic {
+        severity: Severity::Error,
+        line: self.maps[cycle[0]].line,
+        message: format!(
+          "categories form a cycle: {}",
+          cycle
+            .iter()
+            .map(|&idx| self.maps[idx].source.as_str())
+            .chain([self.maps[cycle[0]].source.as_str()])
+            .collect::<Vec<&str>>()
+            .join(" -> ")
+        ),
+      });
+    }
+
+    diagnostics
+  }
+
+  // indices of the maps forming the first cycle found, if any
+  fn find_cycle(&self) -> Option<Vec<usize>> {
+    // depth first search keeping the maps on the current path in `stack`
+    fn visit(
+      almanac: &Almanac,
+      category: &str,
+      stack: &mut Vec<usize>,
+      done: &mut HashSet<String>,
+    ) -> Option<Vec<usize>> {
+      for (idx, map) in almanac.maps.iter().enumerate() {
+        if map.source != category || done.contains(&map.target) {
+          continue;
+        }
+        if let Some(pos) = stack
+          .iter()
+          .position(|&on_path| almanac.maps[on_path].source == map.target)
+        {
+          let mut cycle = stack[pos..].to_vec();
+          cycle.push(idx);
+          return Some(cycle);
+        }
+        if map.target == category {
+          return Some(vec![idx]);
+        }
+        stack.push(idx);
+        let cycle = visit(almanac, &map.target, stack, done);
+        stack.pop();
+        if cycle.is_some() {
+          return cycle;
+        }
+      }
+      done.insert(category.to_string());
+      None
+    }
+
+    let mut done: HashSet<String> = HashSet::new();
+    for map in &self.maps {
+      if !done.contains(&map.source) {
+        let cycle = visit(self, &map.source, &mut Vec::new(), &mut done);
+        if cycle.is_some() {
+          return cycle;
+        }
+      }
+    }
+    None
+  }
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
 pub fn solve() {
   println!("Part 1: {}", part1("inputs/day5/part1").unwrap());
   println!("Part 2: {}", part2("inputs/day5/part1").unwrap());
 }
 
+pub fn run(args: &[String]) {
+  let file = |idx: usize| args.get(idx).map_or("inputs/day5/part1", |s| s.as_str());
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("locations") => print_locations(file(1)),
+    Some("seeds") => print_seeds(&args[1..]),
+    Some("path") => print_path(&args[1..]),
+    Some("validate") => print_diagnostics(file(1)),
+    Some(cmd) => {
+      println!("Unknown day5 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
 fn part1(file: &str) -> io::Result<u64> {
-  let (seeds, maps) = get_seeds_and_maps(file);
-  let final_mapping: Vec<Range> = maps.iter().fold(Vec::new(), |acc, map| merge(&acc, map));
+  let (seeds, final_mapping) = get_seeds_and_mapping(file)?;
 
   let mut lowest_output = u64::MAX;
 
@@ -32,75 +518,116 @@ fn part1(file: &str) -> io::Result<u64> {
 }
 
 fn part2(file: &str) -> io::Result<u64> {
-  let (seeds, maps) = get_seeds_and_maps(file);
-  let final_mapping: Vec<Range> = maps.iter().fold(Vec::new(), |acc, map| merge(&acc, map));
+  let (seeds, final_mapping) = get_seeds_and_mapping(file)?;
 
-  let mut lowest_output = u64::MAX;
-  let pairs: Vec<(u64, u64)> = seeds.chunks(2).map(|chunk| (chunk[0], chunk[1])).collect();
+  let locations = IntervalSet::from_seed_pairs(&seeds)?.map_through(&final_mapping);
+  Ok(locations.min().unwrap_or(u64::MAX))
+}
 
-  for pair in pairs {
-    let srcs = get_srcs_in_range(pair.0, pair.1, &final_mapping);
-    for src in srcs {
-      let output = get_output(src, &final_mapping);
-      lowest_output = cmp::min(lowest_output, output);
-    }
+fn print_locations(file: &str) -> io::Result<()> {
+  let (seeds, final_mapping) = get_seeds_and_mapping(file)?;
+
+  let seeds = IntervalSet::from_seed_pairs(&seeds)?;
+  let locations = seeds.map_through(&final_mapping);
+  println!(
+    "{} seeds in {} intervals map to {} location intervals:",
+    seeds.count(),
+    seeds.intervals.len(),
+    locations.intervals.len()
+  );
+  for (start, end) in &locations.intervals {
+    println!("  [{}, {})", start, end);
   }
-  Ok(lowest_output)
+  Ok(())
 }
 
-fn get_seeds_and_maps(file: &str) -> (Vec<u64>, Vec<Vec<Range>>) {
-  let input = fs::read_to_string(file).unwrap();
-  let mut seeds: Vec<u64> = Vec::new();
-  let mut maps: Vec<Vec<Range>> = Vec::new();
-  let mut tmp_src_to_dst: HashMap<u64, u64> = HashMap::new();
-
-  for line in input.lines() {
-    if line.starts_with("seeds:") {
-      let mut split = line.split(":");
-      split.next(); // skip the `seeds` part
-      let seeds_str = split.next().unwrap();
-      seeds = seeds_str
-        .split_whitespace()
-        .map(|s| s.parse::<u64>().unwrap())
-        .collect();
-    } else if line.ends_with("map:") {
-      if tmp_src_to_dst.len() > 0 {
-        let mut mapping_vec = tmp_src_to_dst
-          .iter()
-          .map(|(src, dst)| Range {
-            src: *src,
-            dst: if *dst == EMPTY { *src } else { *dst },
-          })
-          .collect::<Vec<Range>>();
-        mapping_vec.sort_by(|a, b| a.src.cmp(&b.src));
-        maps.push(mapping_vec);
-      }
-      tmp_src_to_dst.clear();
-    } else if line.len() > 0 {
-      let mut split = line.split_whitespace();
-      let dst = split.next().unwrap().parse::<u64>().unwrap();
-      let src = split.next().unwrap().parse::<u64>().unwrap();
-      let len = split.next().unwrap().parse::<u64>().unwrap();
-      tmp_src_to_dst.insert(src, dst);
-
-      if tmp_src_to_dst.get(&(src + len)) == None {
-        tmp_src_to_dst.insert(src + len, EMPTY);
-      }
+// <start> [len] [file]
+fn print_seeds(args: &[String]) -> io::Result<()> {
+  let number = |idx: usize, default: Option<u64>| match (args.get(idx), default) {
+    (Some(text), _) => text
+      .parse::<u64>()
+      .map_err(|_| invalid_input(format!("malformed number `{}`", text))),
+    (None, Some(default)) => Ok(default),
+    (None, None) => Err(invalid_input(String::from("missing start location"))),
+  };
+  let start = number(0, None)?;
+  let len = number(1, Some(1))?;
+  let file = args.get(2).map_or("inputs/day5/part1", |s| s.as_str());
+  let end = start
+    .checked_add(len)
+    .ok_or_else(|| invalid_input(format!("band {} + {} overflows u64", start, len)))?;
+
+  let almanac = Almanac::parse(file)?;
+  let almanac_seeds = IntervalSet::from_seed_pairs(&almanac.seeds)?;
+  let final_mapping = almanac.compose("seed", "location")?;
+  let values = IntervalSet::new(vec![(start, end)]).preimage_through(&final_mapping);
+  let pass_through = almanac.unmapped("seed", "location", &values)?;
+
+  let print_set = |name: &str, set: &IntervalSet| {
+    println!("{} ({} values):", name, set.count());
+    for (start, end) in &set.intervals {
+      println!("  [{}, {})", start, end);
     }
-  }
-  if tmp_src_to_dst.len() > 0 {
-    let mut mapping_vec = tmp_src_to_dst
+  };
+
+  print_set("Seeds landing in the band", &values);
+  print_set("Unmapped pass-through seeds", &pass_through);
+  print_set(
+    "Almanac seeds landing in the band",
+    &values.intersect(&almanac_seeds),
+  );
+  Ok(())
+}
+
+// <from> <to> [file]
+fn print_path(args: &[String]) -> io::Result<()> {
+  let (from, to) = match args {
+    [from, to, ..] => (from.as_str(), to.as_str()),
+    _ => {
+      return Err(invalid_input(String::from(
+        "expected <from> <to> categories",
+      )))
+    }
+  };
+  let file = args.get(2).map_or("inputs/day5/part1", |s| s.as_str());
+
+  let almanac = Almanac::parse(file)?;
+  let path = almanac.path(from, to)?;
+  let mapping = almanac.compose(from, to)?;
+
+  let mut categories: Vec<&str> = vec![from];
+  categories.extend(path.iter().map(|map| map.target.as_str()));
+  println!("Path: {}", categories.join(" -> "));
+  println!("Composed mapping has {} breakpoints", mapping.len());
+  if from == "seed" {
+    let lowest = almanac
+      .seeds
       .iter()
-      .map(|(src, dst)| Range {
-        src: *src,
-        dst: if *dst == EMPTY { *src } else { *dst },
-      })
-      .collect::<Vec<Range>>();
-    mapping_vec.sort_by(|a, b| a.src.cmp(&b.src));
-    maps.push(mapping_vec);
+      .map(|&seed| get_output(seed, &mapping))
+      .min();
+    println!("Lowest {} for the almanac seeds: {:?}", to, lowest);
+  }
+  Ok(())
+}
+
+fn print_diagnostics(file: &str) -> io::Result<()> {
+  let almanac = Almanac::parse(file)?;
+  let diagnostics = almanac.validate();
+  for diagnostic in &diagnostics {
+    println!("{}", diagnostic);
   }
+  println!(
+    "{} maps, {} diagnostics",
+    almanac.maps.len(),
+    diagnostics.len()
+  );
+  Ok(())
+}
 
-  (seeds, maps)
+fn get_seeds_and_mapping(file: &str) -> io::Result<(Vec<u64>, Vec<Range>)> {
+  let almanac = Almanac::parse(file)?;
+  let mapping = almanac.compose("seed", "location")?;
+  Ok((almanac.seeds, mapping))
 }
 
 fn merge(sorted_a: &Vec<Range>, sorted_b: &Vec<Range>) -> Vec<Range> {
@@ -170,34 +697,17 @@ fn get_output(input: u64, sorted_mapping: &Vec<Range>) -> u64 {
   output
 }
 
-fn get_srcs_in_range(from: u64, len: u64, ranges: &Vec<Range>) -> Vec<u64> {
-  let mut srcs: Vec<u64> = vec![from];
-  for range in ranges {
-    if from + len < range.src {
-      break;
-    }
-
-    if from < range.src && range.src < from + len {
-      srcs.push(range.src);
-    }
-  }
-  srcs.push(from + len);
-  srcs
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
 
   #[test]
   fn test_get_output() {
-    let mut sorted_mapping: Vec<Mapping> = Vec::new();
-    sorted_mapping.push(Mapping { src: 50, dest: 52 });
-    sorted_mapping.push(Mapping { src: 98, dest: 50 });
-    sorted_mapping.push(Mapping {
-      src: 100,
-      dest: 100,
-    });
+    let sorted_mapping: Vec<Range> = vec![
+      Range { src: 50, dst: 52 },
+      Range { src: 98, dst: 50 },
+      Range { src: 100, dst: 100 },
+    ];
     assert_eq!(get_output(0, &sorted_mapping), 0);
     assert_eq!(get_output(40, &sorted_mapping), 40);
     assert_eq!(get_output(50, &sorted_mapping), 52);
@@ -207,4 +717,110 @@ mod tests {
     assert_eq!(get_output(100, &sorted_mapping), 100);
     assert_eq!(get_output(101, &sorted_mapping), 101);
   }
+
+  #[test]
+  fn test_map_through_matches_point_mapping() {
+    let (seeds, final_mapping) = get_seeds_and_mapping("inputs/day5/sample1").unwrap();
+
+    let seed_set = IntervalSet::from_seed_pairs(&seeds).unwrap();
+    let locations = seed_set.map_through(&final_mapping);
+
+    let mut expected: Vec<(u64, u64)> = Vec::new();
+    for &(start, end) in &seed_set.intervals {
+      for seed in start..end {
+        let location = get_output(seed, &final_mapping);
+        expected.push((location, location + 1));
+      }
+    }
+    assert_eq!(locations, IntervalSet::new(expected));
+    assert_eq!(locations.min(), Some(46));
+
+    assert!(IntervalSet::from_seed_pairs(&[79, 14, 55]).is_err());
+    assert!(IntervalSet::from_seed_pairs(&[u64::MAX, 1]).is_err());
+  }
+
+  #[test]
+  fn test_preimage_matches_point_mapping() {
+    let (_, final_mapping) = get_seeds_and_mapping("inputs/day5/sample1").unwrap();
+
+    let band = IntervalSet::new(vec![(40, 60), (90, 95)]);
+    let preimage = band.preimage_through(&final_mapping);
+
+    let mut expected: Vec<(u64, u64)> = Vec::new();
+    for seed in 0..1000 {
+      let location = get_output(seed, &final_mapping);
+      if band.overlaps(location, location + 1).next().is_some() {
+        expected.push((seed, seed + 1));
+      }
+    }
+    let window = IntervalSet::new(vec![(0, 1000)]);
+    assert_eq!(preimage.intersect(&window), IntervalSet::new(expected));
+  }
+
+  #[test]
+  fn test_pass_through_skips_covered_seeds() {
+    let entry = |dst, src, len| MapEntry {
+      dst,
+      src,
+      len,
+      line: 0,
+    };
+    let map = |source: &str, target: &str, entries| CategoryMap {
+      source: source.to_string(),
+      target: target.to_string(),
+      line: 0,
+      entries,
+    };
+    // 0..5 moves away and back, 10..15 is mapped onto itself
+    let almanac = Almanac {
+      seeds: Vec::new(),
+      maps: vec![
+        map("seed", "soil", vec![entry(20, 0, 5), entry(10, 10, 5)]),
+        map("soil", "location", vec![entry(0, 20, 5)]),
+      ],
+    };
+
+    let band = IntervalSet::new(vec![(0, 30)]);
+    assert_eq!(
+      almanac.unmapped("seed", "location", &band).unwrap(),
+      IntervalSet::new(vec![(5, 10), (15, 20), (25, 30)])
+    );
+  }
+
+  #[test]
+  fn test_validate_tracks_furthest_entry() {
+    let entry = |dst, src, len, line| MapEntry {
+      dst,
+      src,
+      len,
+      line,
+    };
+    let almanac = Almanac {
+      seeds: Vec::new(),
+      maps: vec![CategoryMap {
+        source: String::from("seed"),
+        target: String::from("soil"),
+        line: 1,
+        entries: vec![
+          entry(0, 0, 100, 2),
+          entry(50, 10, 10, 3),
+          entry(200, 30, 10, 4),
+          entry(0, 150, 10, 5),
+          entry(0, u64::MAX, 2, 6),
+        ],
+      }],
+    };
+
+    let diagnostics: Vec<String> = almanac.validate().iter().map(|d| d.to_string()).collect();
+    assert_eq!(
+      diagnostics,
+      vec![
+        "Error: line 3: seed-to-soil source range [10, 20) overlaps [0, 100) from line 2",
+        "Error: line 4: seed-to-soil source range [30, 40) overlaps [0, 100) from line 2",
+        "Warning: line 5: seed-to-soil leaves a gap [100, 150) that passes through unmapped",
+        "Error: line 6: seed-to-soil source range 18446744073709551615 + 2 overflows u64",
+      ]
+    );
+    assert!(almanac.compose("seed", "soil").is_err());
+  }
 }
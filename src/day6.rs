@@ -1,77 +1,654 @@
 use std::fs;
 use std::io;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Race {
+  time: u128,
+  distance: u128,
+}
+
+// `--kerning all` lists 2^(columns - 1) kernings
+const MAX_ALL_COLUMNS: usize = 20;
+
+// how the digits of the sheet are split into races: each race joins the
+// given number of consecutive columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kerning {
+  Separate,
+  Joined,
+  Groups(Vec<usize>),
+}
+
+impl Kerning {
+  fn parse(text: &str) -> io::Result<Kerning> {
+    match text {
+      "separate" => Ok(Kerning::Separate),
+      "joined" => Ok(Kerning::Joined),
+      _ => text
+        .split(',')
+        .map(|size| match size.parse::<usize>() {
+          Ok(size) if size > 0 => Ok(size),
+          _ => Err(invalid_input(format!("malformed kerning `{}`", text))),
+        })
+        .collect::<io::Result<Vec<usize>>>()
+        .map(Kerning::Groups),
+    }
+  }
+
+  // every way of splitting `columns` columns into consecutive groups, one
+  // for each subset of the columns - 1 possible cuts
+  fn all(columns: usize) -> io::Result<Vec<Kerning>> {
+    if columns > MAX_ALL_COLUMNS {
+      return Err(invalid_input(format!(
+        "cannot enumerate the kernings of {} columns, at most {} are supported",
+        columns, MAX_ALL_COLUMNS
+      )));
+    }
+    let mut kernings: Vec<Kerning> = Vec::new();
+    for cuts in 0..1_usize << columns.saturating_sub(1) {
+      let mut groups: Vec<usize> = vec![1];
+      for column in 1..columns {
+        if cuts & (1 << (column - 1)) != 0 {
+          groups.push(1);
+        } else {
+          *groups.last_mut().unwrap() += 1;
+        }
+      }
+      kernings.push(Kerning::Groups(groups));
+    }
+    Ok(kernings)
+  }
+
+  fn group_sizes(&self, columns: usize) -> Vec<usize> {
+    match self {
+      Kerning::Separate => vec![1; columns],
+      Kerning::Joined => vec![columns],
+      Kerning::Groups(groups) => groups.clone(),
+    }
+  }
+}
+
+// the raw columns of the sheet, kept as text so any kerning can be applied
+struct Sheet {
+  times: Vec<String>,
+  distances: Vec<String>,
+}
+
+impl Sheet {
+  fn parse(file: &str) -> io::Result<Sheet> {
+    let input = fs::read_to_string(file)?;
+    let mut line_iter = input.lines();
+    let mut columns = |label: &str| -> io::Result<Vec<String>> {
+      let line = line_iter.next().unwrap_or("");
+      let values = line
+        .strip_prefix(label)
+        .ok_or_else(|| invalid_input(format!("expected `{}` line, found `{}`", label, line)))?;
+      let values: Vec<String> = values.split_whitespace().map(String::from).collect();
+      match values
+        .iter()
+        .find(|v| !v.bytes().all(|b| b.is_ascii_digit()))
+      {
+        Some(value) => Err(invalid_input(format!("malformed number `{}`", value))),
+        None => Ok(values),
+      }
+    };
+    let times = columns("Time:")?;
+    let distances = columns("Distance:")?;
+
+    if times.len() != distances.len() {
+      return Err(invalid_input(format!(
+        "{} times but {} distances",
+        times.len(),
+        distances.len()
+      )));
+    }
+    Ok(Sheet { times, distances })
+  }
+
+  fn races(&self, kerning: &Kerning) -> io::Result<Vec<Race>> {
+    let groups = kerning.group_sizes(self.times.len());
+    if groups.iter().sum::<usize>() != self.times.len() {
+      return Err(invalid_input(format!(
+        "kerning {:?} does not cover the {} columns",
+        groups,
+        self.times.len()
+      )));
+    }
+
+    let join = |columns: &[String]| {
+      let digits = columns.concat();
+      digits
+        .parse::<u128>()
+        .map_err(|_| invalid_input(format!("{} does not fit in u128", digits)))
+    };
+    let mut races: Vec<Race> = Vec::new();
+    let mut start = 0;
+    for size in groups {
+      races.push(Race {
+        time: join(&self.times[start..start + size])?,
+        distance: join(&self.distances[start..start + size])?,
+      });
+      start += size;
+    }
+    Ok(races)
+  }
+}
+
+// speed gained per ms of holding, optionally capped
+#[derive(Debug, Clone, Copy)]
+struct Physics {
+  acceleration: u128,
+  speed_cap: Option<u128>,
+}
+
+impl Default for Physics {
+  fn default() -> Physics {
+    Physics {
+      acceleration: 1,
+      speed_cap: None,
+    }
+  }
+}
+
+impl Physics {
+  fn distance(&self, hold: u128, time: u128) -> u128 {
+    if hold > time {
+      return 0;
+    }
+    let speed = self.acceleration.saturating_mul(hold);
+    let speed = self.speed_cap.map_or(speed, |cap| speed.min(cap));
+    speed.saturating_mul(time - hold)
+  }
+}
+
+struct HoldStats {
+  hold: u128,
+  distance: u128,
+  margin: i128,
+  // shortest race this hold would win, if any
+  break_even_time: Option<u128>,
+}
+
+impl HoldStats {
+  fn new(race: Race, hold: u128, distance: &impl Fn(u128, u128) -> u128) -> io::Result<HoldStats> {
+    let travelled = distance(hold, race.time);
+    let margin = if travelled >= race.distance {
+      i128::try_from(travelled - race.distance).ok()
+    } else {
+      i128::try_from(race.distance - travelled).ok().map(|m| -m)
+    };
+    Ok(HoldStats {
+      hold,
+      distance: travelled,
+      margin: margin
+        .ok_or_else(|| invalid_input(format!("margin of hold {} ms does not fit in i128", hold)))?,
+      break_even_time: break_even_time(hold, race.distance, distance),
+    })
+  }
+}
+
+struct RaceAnalysis {
+  race: Race,
+  optimal_hold: u128,
+  best_distance: u128,
+  // first and last winning hold
+  winning: Option<(u128, u128)>,
+}
+
+// works with any `distance(hold, time)` physics function that rises and then
+// falls with the hold, so the peak and both edges of the winning range are
+// found by binary search instead of visiting every hold
+fn analyze(race: Race, distance: impl Fn(u128, u128) -> u128) -> RaceAnalysis {
+  let travelled = |hold: u128| distance(hold, race.time);
+
+  // first hold the next one does not improve on
+  let optimal_hold = partition_point(0, race.time, |hold| {
+    hold == race.time || travelled(hold + 1) <= travelled(hold)
+  });
+  let best_distance = travelled(optimal_hold);
+
+  let winning = (best_distance > race.distance).then(|| {
+    let first = partition_point(0, optimal_hold, |hold| travelled(hold) > race.distance);
+    let last = partition_point(optimal_hold, race.time, |hold| {
+      hold == race.time || travelled(hold + 1) <= race.distance
+    });
+    (first, last)
+  });
+
+  RaceAnalysis {
+    race,
+    optimal_hold,
+    best_distance,
+    winning,
+  }
+}
+
+// smallest value in low..=high where `pred` holds, `pred` must be monotone
+// and hold at `high`
+fn partition_point(mut low: u128, mut high: u128, pred: impl Fn(u128) -> bool) -> u128 {
+  while low < high {
+    let mid = low + (high - low) / 2;
+    if pred(mid) {
+      high = mid;
+    } else {
+      low = mid + 1;
+    }
+  }
+  low
+}
+
+// galloping search over race durations, a boat that never moves never wins
+fn break_even_time(
+  hold: u128,
+  record: u128,
+  distance: &impl Fn(u128, u128) -> u128,
+) -> Option<u128> {
+  let mut low = hold;
+  let mut step: u128 = 1;
+  let mut high = loop {
+    let time = hold.checked_add(step)?;
+    if distance(hold, time) > record {
+      break time;
+    }
+    low = time;
+    step = step.checked_mul(2)?;
+  };
+  // distance(hold, low) <= record < distance(hold, high)
+  while high - low > 1 {
+    let mid = low + (high - low) / 2;
+    if distance(hold, mid) > record {
+      high = mid;
+    } else {
+      low = mid;
+    }
+  }
+  Some(high)
+}
+
 pub fn solve() {
   println!("Part 1: {}", part1("inputs/day6/part1").unwrap());
-  println!("Part 1: {}", part2("inputs/day6/part1").unwrap());
-}
-
-fn part1(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
-  let mut line_iter = input.lines();
-  let times = line_iter
-    .next()
-    .unwrap()
-    .split(":")
-    .nth(1)
-    .unwrap()
-    .split_whitespace()
-    .map(|s| s.parse::<u64>().unwrap())
-    .collect::<Vec<u64>>();
-  let distances = line_iter
-    .next()
-    .unwrap()
-    .split(":")
-    .nth(1)
-    .unwrap()
-    .split_whitespace()
-    .map(|s| s.parse::<u64>().unwrap())
-    .collect::<Vec<u64>>();
-
-  let mut total: u64 = 1;
-  for (idx, time) in times.iter().enumerate() {
-    let distance = distances[idx];
-    let possible_options = count_possible_options(*time, distance);
-    total *= possible_options;
+  println!("Part 2: {}", part2("inputs/day6/part1").unwrap());
+}
+
+pub fn run(args: &[String]) {
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("races") => print_races(&args[1..]),
+    Some("analyze") => print_analysis(&args[1..]),
+    Some(cmd) => {
+      println!("Unknown day6 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
+fn part1(file: &str) -> io::Result<u128> {
+  product_of_options(&Sheet::parse(file)?, &Kerning::Separate)
+}
+
+fn part2(file: &str) -> io::Result<u128> {
+  product_of_options(&Sheet::parse(file)?, &Kerning::Joined)
+}
+
+fn product_of_options(sheet: &Sheet, kerning: &Kerning) -> io::Result<u128> {
+  let mut total: u128 = 1;
+  for race in sheet.races(kerning)? {
+    total = total
+      .checked_mul(count_possible_options(race)?)
+      .ok_or_else(|| invalid_input(String::from("product of options overflows u128")))?;
   }
   Ok(total)
 }
 
-fn part2(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
-  let mut line_iter = input.lines();
-  let time = line_iter
-    .next()
-    .unwrap()
-    .split(":")
-    .nth(1)
-    .unwrap()
-    .replace(" ", "")
-    .parse::<u64>()
-    .unwrap();
-  let distance = line_iter
-    .next()
-    .unwrap()
-    .split(":")
-    .nth(1)
-    .unwrap()
-    .replace(" ", "")
-    .parse::<u64>()
-    .unwrap();
-
-  Ok(count_possible_options(time, distance))
-}
-
-fn count_possible_options(time: u64, distance: u64) -> u64 {
-  let delta: f64 = (time * time - 4 * distance) as f64;
-  let sqrt_delta = delta.sqrt();
-  let x1 = (time as f64 - sqrt_delta) / 2.0;
-  let x2 = (time as f64 + sqrt_delta) / 2.0;
-  let x1 = if x1.ceil() == x1 { x1 + 1.0 } else { x1.ceil() };
-  let x2 = if x2.floor() == x2 {
-    x2 - 1.0
-  } else {
-    x2.floor()
+// [--kerning separate|joined|all|N,N,...]... [file]
+fn print_races(args: &[String]) -> io::Result<()> {
+  let mut kernings: Vec<String> = Vec::new();
+  let mut file = "inputs/day6/part1";
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--kerning" => kernings.push(iter.next().cloned().unwrap_or_default()),
+      _ => file = arg,
+    }
+  }
+
+  let sheet = Sheet::parse(file)?;
+  let mut batch: Vec<Kerning> = Vec::new();
+  if kernings.is_empty() {
+    batch = vec![Kerning::Separate, Kerning::Joined];
+  }
+  for kerning in kernings {
+    match kerning.as_str() {
+      "all" => batch.extend(Kerning::all(sheet.times.len())?),
+      _ => batch.push(Kerning::parse(&kerning)?),
+    }
+  }
+
+  for kerning in batch {
+    let races = sheet.races(&kerning)?;
+    println!("Kerning {:?}:", kerning.group_sizes(sheet.times.len()));
+    for race in &races {
+      println!(
+        "  {} ms, {} mm: {} options",
+        race.time,
+        race.distance,
+        count_possible_options(*race)?
+      );
+    }
+    println!("  product: {}", product_of_options(&sheet, &kerning)?);
+  }
+
+  Ok(())
+}
+
+// [--kerning K] [--acceleration N] [--speed-cap N] [--holds FIRST..=LAST]
+// [--format table|csv] [file]
+fn print_analysis(args: &[String]) -> io::Result<()> {
+  let mut kerning = Kerning::Separate;
+  let mut physics = Physics::default();
+  let mut holds: Option<(u128, u128)> = None;
+  let mut csv = false;
+  let mut file = "inputs/day6/part1";
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    let mut value = || {
+      iter
+        .next()
+        .ok_or_else(|| invalid_input(format!("missing value for {}", arg)))
+    };
+    let number = |text: &str| {
+      text
+        .parse::<u128>()
+        .map_err(|_| invalid_input(format!("malformed number `{}`", text)))
+    };
+    match arg.as_str() {
+      "--kerning" => kerning = Kerning::parse(value()?)?,
+      "--acceleration" => physics.acceleration = number(value()?)?,
+      "--speed-cap" => physics.speed_cap = Some(number(value()?)?),
+      "--holds" => {
+        let range = value()?;
+        let (first, last) = range
+          .split_once("..=")
+          .ok_or_else(|| invalid_input(format!("expected FIRST..=LAST, found `{}`", range)))?;
+        holds = Some((number(first)?, number(last)?));
+      }
+      "--format" => {
+        csv = match value()?.as_str() {
+          "table" => false,
+          "csv" => true,
+          format => return Err(invalid_input(format!("unknown format `{}`", format))),
+        }
+      }
+      _ => file = arg,
+    }
+  }
+
+  let sheet = Sheet::parse(file)?;
+  let distance = |hold, time| physics.distance(hold, time);
+  let analyses: Vec<RaceAnalysis> = sheet
+    .races(&kerning)?
+    .into_iter()
+    .map(|race| analyze(race, distance))
+    .collect();
+
+  // the requested holds of a race, streamed rather than collected
+  let hold_range = |race: Race| {
+    holds
+      .map(|(first, last)| first..=last.min(race.time))
+      .into_iter()
+      .flatten()
+  };
+  let format_time = |time: Option<u128>| time.map_or(String::from("-"), |t| t.to_string());
+  let format_winning = |winning: Option<(u128, u128)>| match winning {
+    Some((first, last)) => (first.to_string(), last.to_string()),
+    None => (String::from("-"), String::from("-")),
   };
-  x2 as u64 - x1 as u64 + 1
+
+  if csv {
+    if holds.is_none() {
+      println!("race,time,record,optimal_hold,best_distance,first_winning,last_winning");
+      for (idx, analysis) in analyses.iter().enumerate() {
+        let (first, last) = format_winning(analysis.winning);
+        println!(
+          "{},{},{},{},{},{},{}",
+          idx + 1,
+          analysis.race.time,
+          analysis.race.distance,
+          analysis.optimal_hold,
+          analysis.best_distance,
+          first,
+          last
+        );
+      }
+      return Ok(());
+    }
+
+    println!("race,time,record,hold,distance,margin,wins,break_even_time");
+    for (idx, analysis) in analyses.iter().enumerate() {
+      for hold in hold_range(analysis.race) {
+        let stats = HoldStats::new(analysis.race, hold, &distance)?;
+        println!(
+          "{},{},{},{},{},{},{},{}",
+          idx + 1,
+          analysis.race.time,
+          analysis.race.distance,
+          stats.hold,
+          stats.distance,
+          stats.margin,
+          stats.margin > 0,
+          format_time(stats.break_even_time)
+        );
+      }
+    }
+    return Ok(());
+  }
+
+  for (idx, analysis) in analyses.iter().enumerate() {
+    println!(
+      "Race {}: {} ms, record {} mm",
+      idx + 1,
+      analysis.race.time,
+      analysis.race.distance
+    );
+    println!(
+      "  optimal hold {} ms travels {} mm",
+      analysis.optimal_hold, analysis.best_distance
+    );
+    match analysis.winning {
+      Some((first, last)) => println!("  winning holds {}..={} ms", first, last),
+      None => println!("  no hold beats the record"),
+    }
+    if holds.is_none() {
+      continue;
+    }
+    println!(
+      "  {:>8} {:>12} {:>12} {:>10}",
+      "hold", "distance", "margin", "break-even"
+    );
+    for hold in hold_range(analysis.race) {
+      let stats = HoldStats::new(analysis.race, hold, &distance)?;
+      println!(
+        "  {:>8} {:>12} {:>12} {:>10}",
+        stats.hold,
+        stats.distance,
+        stats.margin,
+        format_time(stats.break_even_time)
+      );
+    }
+  }
+
+  Ok(())
+}
+
+fn beats(race: Race, hold: u128) -> bool {
+  // a product too large for u128 is certainly larger than the record
+  hold
+    .checked_mul(race.time - hold)
+    .is_none_or(|distance| distance > race.distance)
+}
+
+// holding `h` travels h * (time - h), which peaks at a = time / 2. writing
+// time = 2a + e, the hold a - k travels best - k * (k + e), so the winning
+// holds are a - k for k * (k + e) < best - distance. the integer square root
+// of that margin gives k to within one, and the fix-ups make it exact
+fn count_possible_options(race: Race) -> io::Result<u128> {
+  let a = race.time / 2;
+  let best = a.checked_mul(race.time - a).ok_or_else(|| {
+    invalid_input(format!(
+      "race of {} ms is too long for u128 distances",
+      race.time
+    ))
+  })?;
+  if best <= race.distance {
+    return Ok(0);
+  }
+
+  // smallest winning hold
+  let mut first = a - (best - race.distance).isqrt().min(a);
+  while first > 0 && beats(race, first - 1) {
+    first -= 1;
+  }
+  while !beats(race, first) {
+    first += 1;
+  }
+
+  // winning holds are symmetric around the peak
+  Ok(race.time - 2 * first + 1)
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn brute_force(race: Race) -> u128 {
+    (0..=race.time)
+      .filter(|hold| hold * (race.time - hold) > race.distance)
+      .count() as u128
+  }
+
+  #[test]
+  fn test_count_possible_options_matches_brute_force() {
+    for time in 0..80 {
+      for distance in 0..=time * time / 4 + 2 {
+        let race = Race { time, distance };
+        assert_eq!(
+          count_possible_options(race).unwrap(),
+          brute_force(race),
+          "{:?}",
+          race
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_count_possible_options_large_races() {
+    // the exact boundaries of a huge race, checked around both roots
+    let race = Race {
+      time: 9_999_999_999_999_999_999,
+      distance: 12_345_678_901_234_567_890_123_456_789,
+    };
+    let options = count_possible_options(race).unwrap();
+    let first = (race.time + 1 - options) / 2;
+    assert!(beats(race, first) && !beats(race, first - 1));
+    assert!(beats(race, race.time - first) && !beats(race, race.time - first + 1));
+  }
+
+  #[test]
+  fn test_analyze_matches_every_hold() {
+    let physics = [
+      Physics::default(),
+      Physics {
+        acceleration: 3,
+        speed_cap: None,
+      },
+      Physics {
+        acceleration: 2,
+        speed_cap: Some(7),
+      },
+      Physics {
+        acceleration: 0,
+        speed_cap: None,
+      },
+    ];
+    for physics in physics {
+      for time in 0..40 {
+        for distance in (0..time * time).step_by(5) {
+          let race = Race { time, distance };
+          let travelled: Vec<u128> = (0..=time)
+            .map(|hold| physics.distance(hold, time))
+            .collect();
+          let best = *travelled.iter().max().unwrap();
+          let wins: Vec<u128> = (0..=time)
+            .filter(|&h| travelled[h as usize] > distance)
+            .collect();
+
+          let analysis = analyze(race, |hold, time| physics.distance(hold, time));
+          assert_eq!(analysis.best_distance, best, "{:?} {:?}", physics, race);
+          assert_eq!(travelled[analysis.optimal_hold as usize], best);
+          assert_eq!(
+            analysis.winning,
+            wins
+              .first()
+              .zip(wins.last())
+              .map(|(&first, &last)| (first, last)),
+            "{:?} {:?}",
+            physics,
+            race
+          );
+        }
+      }
+    }
+
+    // a margin past i128 is an error rather than a wrapped value
+    let race = Race {
+      time: u128::MAX,
+      distance: 0,
+    };
+    let physics = Physics::default();
+    assert!(HoldStats::new(race, 1 << 64, &|hold, time| physics.distance(hold, time)).is_err());
+  }
+
+  #[test]
+  fn test_kernings() {
+    let sheet = Sheet::parse("inputs/day6/sample1").unwrap();
+    assert_eq!(product_of_options(&sheet, &Kerning::Separate).unwrap(), 288);
+    assert_eq!(product_of_options(&sheet, &Kerning::Joined).unwrap(), 71503);
+    assert_eq!(
+      sheet.races(&Kerning::parse("1,2").unwrap()).unwrap(),
+      vec![
+        Race {
+          time: 7,
+          distance: 9
+        },
+        Race {
+          time: 1530,
+          distance: 40200
+        }
+      ]
+    );
+    assert_eq!(Kerning::all(3).unwrap().len(), 4);
+    assert!(Kerning::all(MAX_ALL_COLUMNS + 1).is_err());
+  }
+
+  #[test]
+  fn test_unknown_format() {
+    let args = |format: &str| vec![String::from("--format"), String::from(format)];
+    let err = print_analysis(&args("json")).unwrap_err();
+    assert_eq!(err.to_string(), "unknown format `json`");
+    assert!(print_analysis(&[String::from("--format")]).is_err());
+  }
 }
@@ -1,22 +1,277 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
 use std::fs;
 use std::io;
 
-struct Row {
-  kind: u64,
-  hand: String,
-  bid: u64,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TieBreak {
+  // compare the cards one by one in the order they were dealt
+  Sequential,
+  // compare the biggest groups first, then the remaining cards by rank
+  Poker,
 }
 
-impl Row {
-  fn new(kind: u64, hand: &str, bid: u64) -> Row {
-    Row {
-      kind,
-      hand: String::from(hand),
-      bid,
+#[derive(Debug, Clone)]
+struct Category {
+  // group sizes, largest first, e.g. [3, 2] for a full house
+  counts: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Categories {
+  // any multiset of group sizes is a category, bigger groups rank higher
+  // (compared largest group first), which works for hands of any length
+  ByCounts,
+  // only the listed categories are allowed, weakest first
+  Listed(Vec<Category>),
+}
+
+#[derive(Debug, Clone)]
+struct Ruleset {
+  // weakest card first
+  card_order: Vec<char>,
+  wild: Option<char>,
+  categories: Categories,
+  tie_break: TieBreak,
+}
+
+// what the jokers of a hand were turned into
+#[derive(Debug, Clone, Default)]
+struct JokerAssignment {
+  // jokers added to each natural group, by card rank
+  joined: Vec<(usize, usize)>,
+  // sizes of groups made of jokers only
+  new_groups: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Hand {
+  cards: String,
+  counts: Vec<usize>,
+  // sort key of the category, compared before the tie-break
+  category: Vec<usize>,
+  tie_break: Vec<usize>,
+  jokers: JokerAssignment,
+}
+
+impl Ord for Hand {
+  fn cmp(&self, other: &Hand) -> Ordering {
+    self
+      .category
+      .cmp(&other.category)
+      .then_with(|| self.tie_break.cmp(&other.tie_break))
+  }
+}
+
+impl PartialOrd for Hand {
+  fn partial_cmp(&self, other: &Hand) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl PartialEq for Hand {
+  fn eq(&self, other: &Hand) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for Hand {}
+
+impl Category {
+  fn parse(counts: &str) -> io::Result<Category> {
+    let mut counts = counts
+      .chars()
+      .map(|c| {
+        c.to_digit(10)
+          .filter(|&count| count > 0)
+          .map(|count| count as usize)
+          .ok_or_else(|| invalid_input(format!("malformed category `{}`", counts)))
+      })
+      .collect::<io::Result<Vec<usize>>>()?;
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(Category { counts })
+  }
+}
+
+fn category_name(counts: &[usize]) -> String {
+  let name = match counts {
+    [5] => "five of a kind",
+    [4, 1] => "four of a kind",
+    [3, 2] => "full house",
+    [3, 1, 1] => "three of a kind",
+    [2, 2, 1] => "two pair",
+    [2, 1, 1, 1] => "one pair",
+    [1, 1, 1, 1, 1] => "high card",
+    _ => {
+      let counts: Vec<String> = counts.iter().map(|c| c.to_string()).collect();
+      return counts.join("+");
+    }
+  };
+  name.to_string()
+}
+
+impl Ruleset {
+  fn part1() -> Ruleset {
+    Ruleset {
+      card_order: "23456789TJQKA".chars().collect(),
+      wild: None,
+      categories: Categories::ByCounts,
+      tie_break: TieBreak::Sequential,
+    }
+  }
+
+  fn part2() -> Ruleset {
+    Ruleset {
+      card_order: "J23456789TQKA".chars().collect(),
+      wild: Some('J'),
+      categories: Categories::ByCounts,
+      tie_break: TieBreak::Sequential,
+    }
+  }
+
+  fn rank(&self, card: char) -> io::Result<usize> {
+    self
+      .card_order
+      .iter()
+      .position(|&c| c == card)
+      .ok_or_else(|| invalid_input(format!("unknown card `{}`", card)))
+  }
+
+  fn evaluate(&self, cards: &str) -> io::Result<Hand> {
+    let ranks = cards
+      .chars()
+      .map(|card| self.rank(card))
+      .collect::<io::Result<Vec<usize>>>()?;
+    let wild_rank = self.wild.map(|wild| self.rank(wild)).transpose()?;
+
+    // group sizes of the natural cards, jokers are assigned afterwards
+    let mut rank_counts: Vec<usize> = vec![0; self.card_order.len()];
+    let mut jokers = 0;
+    for &rank in &ranks {
+      if Some(rank) == wild_rank {
+        jokers += 1;
+      } else {
+        rank_counts[rank] += 1;
+      }
+    }
+    // biggest groups first, higher ranks first among equal groups
+    let mut groups: Vec<(usize, usize)> = (0..rank_counts.len())
+      .filter(|&rank| rank_counts[rank] > 0)
+      .map(|rank| (rank, rank_counts[rank]))
+      .collect();
+    groups.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+
+    let (category, counts, assignment) = match &self.categories {
+      Categories::ByCounts => {
+        // all jokers joining the biggest group always gives the best counts
+        let assignment = match groups.first() {
+          Some(&(rank, _)) if jokers > 0 => JokerAssignment {
+            joined: vec![(rank, jokers)],
+            new_groups: Vec::new(),
+          },
+          None if jokers > 0 => JokerAssignment {
+            joined: Vec::new(),
+            new_groups: vec![jokers],
+          },
+          _ => JokerAssignment::default(),
+        };
+        let counts = assignment.apply(&groups);
+        (counts.clone(), counts, assignment)
+      }
+      Categories::Listed(categories) => JokerAssignment::all(&groups, jokers)
+        .into_iter()
+        .filter_map(|assignment| {
+          let counts = assignment.apply(&groups);
+          let category = categories.iter().position(|c| c.counts == counts)?;
+          Some((vec![category], counts, assignment))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .ok_or_else(|| invalid_input(format!("hand {} matches no category", cards)))?,
+    };
+
+    let tie_break = match self.tie_break {
+      TieBreak::Sequential => ranks,
+      TieBreak::Poker => groups
+        .iter()
+        .flat_map(|&(rank, count)| std::iter::repeat_n(rank, count))
+        .collect(),
+    };
+
+    Ok(Hand {
+      cards: cards.to_string(),
+      counts,
+      category,
+      tie_break,
+      jokers: assignment,
+    })
+  }
+}
+
+impl JokerAssignment {
+  fn apply(&self, groups: &[(usize, usize)]) -> Vec<usize> {
+    let mut counts: Vec<usize> = groups
+      .iter()
+      .map(|&(rank, count)| {
+        let joined = self.joined.iter().find(|(r, _)| *r == rank);
+        count + joined.map_or(0, |(_, jokers)| *jokers)
+      })
+      .collect();
+    counts.extend(self.new_groups.iter().copied());
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    counts
+  }
+
+  // every way to spread the jokers over the natural groups, with whatever
+  // is left over forming new groups
+  fn all(groups: &[(usize, usize)], jokers: usize) -> Vec<JokerAssignment> {
+    let mut assignments: Vec<JokerAssignment> = Vec::new();
+    let mut joined: Vec<(usize, usize)> = Vec::new();
+    spread(groups, jokers, &mut joined, &mut assignments);
+    assignments
+  }
+}
+
+fn spread(
+  groups: &[(usize, usize)],
+  jokers: usize,
+  joined: &mut Vec<(usize, usize)>,
+  assignments: &mut Vec<JokerAssignment>,
+) {
+  match groups.split_first() {
+    Some((&(rank, _), rest)) => {
+      for count in 0..=jokers {
+        if count > 0 {
+          joined.push((rank, count));
+        }
+        spread(rest, jokers - count, joined, assignments);
+        if count > 0 {
+          joined.pop();
+        }
+      }
+    }
+    None => {
+      for new_groups in partitions(jokers, jokers) {
+        assignments.push(JokerAssignment {
+          joined: joined.clone(),
+          new_groups,
+        });
+      }
+    }
+  }
+}
+
+// integer partitions of `n` into parts no bigger than `max`, largest first
+fn partitions(n: usize, max: usize) -> Vec<Vec<usize>> {
+  if n == 0 {
+    return vec![Vec::new()];
+  }
+  let mut result: Vec<Vec<usize>> = Vec::new();
+  for part in (1..=max.min(n)).rev() {
+    for mut rest in partitions(n - part, part) {
+      rest.insert(0, part);
+      result.push(rest);
     }
   }
+  result
 }
 
 pub fn solve() {
@@ -24,168 +279,277 @@ pub fn solve() {
   println!("Part 2: {}", part2("inputs/day7/part1").unwrap());
 }
 
+pub fn run(args: &[String]) {
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("play") => parse_ruleset_args(&args[1..]).and_then(|(ruleset, file)| {
+      println!("Total: {}", total_winnings(&file, &ruleset)?);
+      Ok(())
+    }),
+    Some("explain") => {
+      parse_ruleset_args(&args[1..]).and_then(|(ruleset, file)| explain(&file, &ruleset))
+    }
+    Some(cmd) => {
+      println!("Unknown day7 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
 fn part1(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
-  let mut rows: Vec<Row> = Vec::new();
-
-  for line in input.lines() {
-    let mut split = line.split_whitespace();
-    let hand = split
-      .next()
-      .unwrap()
-      .replace("A", "E")
-      .replace("K", "D")
-      .replace("Q", "C")
-      .replace("J", "B")
-      .replace("T", "A");
-    let bid = split.next().unwrap().parse::<u64>().unwrap();
-    let kind = get_kind(&hand);
-    let row = Row::new(kind, &hand, bid);
-    rows.push(row);
-  }
-
-  rows.sort_by(compare);
+  total_winnings(file, &Ruleset::part1())
+}
+
+fn part2(file: &str) -> io::Result<u64> {
+  total_winnings(file, &Ruleset::part2())
+}
+
+fn total_winnings(file: &str, ruleset: &Ruleset) -> io::Result<u64> {
+  let mut hands = parse_hands(file, ruleset)?;
+  hands.sort();
+
   let mut total = 0;
-  for (idx, row) in rows.iter().enumerate() {
-    total += (idx + 1) as u64 * row.bid;
+  for (idx, (_, bid)) in hands.iter().enumerate() {
+    total += (idx + 1) as u64 * bid;
   }
-
   Ok(total)
 }
 
-fn part2(file: &str) -> io::Result<u64> {
-  let input = fs::read_to_string(file)?;
-  let mut rows: Vec<Row> = Vec::new();
-
-  for line in input.lines() {
-    let mut split = line.split_whitespace();
-    let hand = split
-      .next()
-      .unwrap()
-      .replace("A", "E")
-      .replace("K", "D")
-      .replace("Q", "C")
-      .replace("J", "0") // joker
-      .replace("T", "A");
-    let bid = split.next().unwrap().parse::<u64>().unwrap();
-    let kind = get_kind_part_2(&hand);
-    let row = Row::new(kind, &hand, bid);
-    rows.push(row);
-  }
-
-  rows.sort_by(compare);
+fn explain(file: &str, ruleset: &Ruleset) -> io::Result<()> {
+  let mut hands = parse_hands(file, ruleset)?;
+  hands.sort();
+
+  let width = hands
+    .iter()
+    .map(|(hand, _)| hand.cards.len())
+    .max()
+    .unwrap_or(0);
   let mut total = 0;
-  for (idx, row) in rows.iter().enumerate() {
-    total += (idx + 1) as u64 * row.bid;
+  for (idx, (hand, bid)) in hands.iter().enumerate() {
+    let rank = idx + 1;
+    let winnings = rank as u64 * bid;
+    total += winnings;
+
+    let mut jokers: Vec<String> = hand
+      .jokers
+      .joined
+      .iter()
+      .map(|&(rank, count)| format!("{}x{}", count, ruleset.card_order[rank]))
+      .collect();
+    jokers.extend(
+      hand
+        .jokers
+        .new_groups
+        .iter()
+        .map(|count| format!("{}x new", count)),
+    );
+    let jokers = if jokers.is_empty() {
+      String::from("-")
+    } else {
+      jokers.join(" ")
+    };
+
+    println!(
+      "{:>5} {:<width$} {:<16} jokers: {:<10} bid {:>5} winnings {}",
+      rank,
+      hand.cards,
+      category_name(&hand.counts),
+      jokers,
+      bid,
+      winnings,
+      width = width
+    );
   }
+  println!("Total: {}", total);
 
-  Ok(total)
+  Ok(())
 }
 
-fn get_kind(hand: &str) -> u64 {
-  let mut counts: HashMap<char, u64> = HashMap::new();
-  for c in hand.chars() {
-    let count = counts.entry(c).or_insert(0);
-    *count += 1;
-  }
-  let mut is_five_of_a_kind = false;
-  let mut is_four_of_a_kind = false;
-  let mut has_triplet = false;
-  let mut pair_count = 0;
-
-  for (_, count) in counts {
-    if count == 5 {
-      is_five_of_a_kind = true;
-      break;
-    } else if count == 4 {
-      is_four_of_a_kind = true;
-      break;
-    } else if count == 3 {
-      has_triplet = true;
-    } else if count == 2 {
-      pair_count += 1;
-    }
+fn parse_hands(file: &str, ruleset: &Ruleset) -> io::Result<Vec<(Hand, u64)>> {
+  let input = fs::read_to_string(file)?;
+  let mut hands: Vec<(Hand, u64)> = Vec::new();
+
+  for (idx, line) in input.lines().enumerate() {
+    let error = |message: String| invalid_input(format!("line {}: {}", idx + 1, message));
+    let (cards, bid) = line
+      .split_once(' ')
+      .ok_or_else(|| error(format!("malformed hand `{}`", line)))?;
+    let bid = bid
+      .trim()
+      .parse::<u64>()
+      .map_err(|_| error(format!("malformed bid `{}`", bid)))?;
+    let hand = ruleset
+      .evaluate(cards)
+      .map_err(|err| error(err.to_string()))?;
+    hands.push((hand, bid));
   }
-  let mut kind: u64 = 0;
-  if is_five_of_a_kind {
-    kind = 6
-  } else if is_four_of_a_kind {
-    kind = 5;
-  } else if has_triplet && pair_count == 1 {
-    kind = 4;
-  } else if has_triplet {
-    kind = 3;
-  } else if pair_count == 2 {
-    kind = 2;
-  } else if pair_count == 1 {
-    kind = 1;
-  }
-  kind
-}
-
-fn get_kind_part_2(hand: &str) -> u64 {
-  let mut counts: HashMap<char, u64> = HashMap::new();
-  let mut joker_count = 0;
-
-  let mut max_count = 0;
-  let mut max_count_key: char = '0';
-  for c in hand.chars() {
-    if c == '0' {
-      joker_count += 1;
-      continue;
-    }
-    let count = counts.entry(c).or_insert(0);
-    *count += 1;
-    if *count > max_count {
-      max_count = *count;
-      max_count_key = c;
+
+  Ok(hands)
+}
+
+// [--order 23456789TJQKA] [--wild J] [--categories counts|11111,2111,...]
+// [--tie-break sequential|poker] [file]
+fn parse_ruleset_args(args: &[String]) -> io::Result<(Ruleset, String)> {
+  let mut ruleset = Ruleset::part1();
+  let mut file = String::from("inputs/day7/part1");
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    let mut value = || {
+      iter
+        .next()
+        .ok_or_else(|| invalid_input(format!("missing value for {}", arg)))
+    };
+    match arg.as_str() {
+      "--order" => ruleset.card_order = value()?.chars().collect(),
+      "--wild" => ruleset.wild = value()?.chars().next(),
+      "--categories" => {
+        ruleset.categories = match value()?.as_str() {
+          "counts" => Categories::ByCounts,
+          list => Categories::Listed(
+            list
+              .split(',')
+              .map(Category::parse)
+              .collect::<io::Result<Vec<Category>>>()?,
+          ),
+        }
+      }
+      "--tie-break" => {
+        ruleset.tie_break = match value()?.as_str() {
+          "sequential" => TieBreak::Sequential,
+          "poker" => TieBreak::Poker,
+          mode => return Err(invalid_input(format!("unknown tie-break `{}`", mode))),
+        }
+      }
+      _ => file = arg.clone(),
     }
   }
-  let count = counts.entry(max_count_key).or_insert(0);
-  *count += joker_count;
-
-  let mut is_five_of_a_kind = false;
-  let mut is_four_of_a_kind = false;
-  let mut has_triplet = false;
-  let mut pair_count = 0;
-
-  if joker_count == 5 {
-    return 6;
-  }
-
-  for (_, count) in counts {
-    if count == 5 {
-      is_five_of_a_kind = true;
-      break;
-    } else if count == 4 {
-      is_four_of_a_kind = true;
-      break;
-    } else if count == 3 {
-      has_triplet = true;
-    } else if count == 2 {
-      pair_count += 1;
-    }
+
+  Ok((ruleset, file))
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn listed(categories: &str) -> Categories {
+    Categories::Listed(
+      categories
+        .split(',')
+        .map(|counts| Category::parse(counts).unwrap())
+        .collect(),
+    )
   }
-  let mut kind: u64 = 0;
-  if is_five_of_a_kind {
-    kind = 6
-  } else if is_four_of_a_kind {
-    kind = 5;
-  } else if has_triplet && pair_count == 1 {
-    kind = 4;
-  } else if has_triplet {
-    kind = 3;
-  } else if pair_count == 2 {
-    kind = 2;
-  } else if pair_count == 1 {
-    kind = 1;
+
+  #[test]
+  fn test_rulesets_on_sample() {
+    assert_eq!(
+      total_winnings("inputs/day7/sample1", &Ruleset::part1()).unwrap(),
+      6440
+    );
+    assert_eq!(
+      total_winnings("inputs/day7/sample1", &Ruleset::part2()).unwrap(),
+      5905
+    );
+
+    // listing the usual categories ranks exactly like the group counts
+    let ruleset = Ruleset {
+      categories: listed("11111,2111,221,311,32,41,5"),
+      ..Ruleset::part2()
+    };
+    assert_eq!(
+      total_winnings("inputs/day7/sample1", &ruleset).unwrap(),
+      5905
+    );
   }
-  kind
-}
 
-fn compare(a: &Row, b: &Row) -> Ordering {
-  if a.kind == b.kind {
-    return a.hand.cmp(&b.hand);
+  #[test]
+  fn test_listed_categories() {
+    let ruleset = Ruleset {
+      categories: listed("11111,2111"),
+      ..Ruleset::part2()
+    };
+    // the joker pairs up with a natural card, the strongest listed category
+    let hand = ruleset.evaluate("J2345").unwrap();
+    assert_eq!(hand.category, vec![1]);
+    assert_eq!(hand.counts, vec![2, 1, 1, 1]);
+    assert_eq!(ruleset.evaluate("23456").unwrap().category, vec![0]);
+    // two pair is not listed
+    assert!(ruleset.evaluate("KK677").is_err());
+  }
+
+  #[test]
+  fn test_joker_assignments() {
+    assert_eq!(
+      partitions(4, 4),
+      vec![
+        vec![4],
+        vec![3, 1],
+        vec![2, 2],
+        vec![2, 1, 1],
+        vec![1, 1, 1, 1]
+      ]
+    );
+    assert_eq!(partitions(3, 1), vec![vec![1, 1, 1]]);
+
+    let assignments = JokerAssignment::all(&[(5, 2)], 2);
+    let mut counts: Vec<Vec<usize>> = assignments
+      .iter()
+      .map(|assignment| assignment.apply(&[(5, 2)]))
+      .collect();
+    counts.sort();
+    assert_eq!(counts, vec![vec![2, 1, 1], vec![2, 2], vec![3, 1], vec![4]]);
+  }
+
+  #[test]
+  fn test_poker_tie_break() {
+    let sequential = Ruleset::part1();
+    let poker = Ruleset {
+      tie_break: TieBreak::Poker,
+      ..Ruleset::part1()
+    };
+    let compare = |ruleset: &Ruleset, a: &str, b: &str| {
+      ruleset
+        .evaluate(a)
+        .unwrap()
+        .cmp(&ruleset.evaluate(b).unwrap())
+    };
+
+    assert_eq!(compare(&sequential, "33332", "2AAAA"), Ordering::Greater);
+    assert_eq!(compare(&poker, "33332", "2AAAA"), Ordering::Less);
+    // the pair decides before the kickers
+    assert_eq!(compare(&poker, "KK234", "QQA98"), Ordering::Greater);
+    assert_eq!(compare(&poker, "AAAA2", "2AAAA"), Ordering::Equal);
+  }
+
+  #[test]
+  fn test_hands_of_other_lengths() {
+    let ruleset = Ruleset::part2();
+    let hand = |cards: &str| ruleset.evaluate(cards).unwrap();
+
+    // bigger groups rank higher whatever the hand size
+    assert_eq!(hand("AAA").counts, vec![3]);
+    assert!(hand("2224") > hand("AAKK"));
+    assert!(hand("AAKK") > hand("AKQT98"));
+    assert!(hand("223") > hand("AK"));
+    // jokers join the biggest group, or form one of their own
+    assert_eq!(hand("J2233").counts, vec![3, 2]);
+    assert_eq!(hand("JJJJJJ").counts, vec![6]);
+    assert_eq!(hand("JJJJJJ").jokers.new_groups, vec![6]);
+
+    assert_eq!(category_name(&hand("KTJJT").counts), "four of a kind");
+    assert_eq!(category_name(&hand("2224").counts), "3+1");
+    assert_eq!(category_name(&hand("JJJJJJ").counts), "6");
   }
-  a.kind.cmp(&b.kind)
 }
@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::fs;
 use std::io;
 
@@ -7,6 +6,179 @@ use std::io;
 struct Node {
   left: String,
   right: String,
+  line: usize,
+}
+
+// comma separated globs over node labels, `?` matches any one character
+// and `*` any run of characters
+struct NodePattern {
+  globs: Vec<Vec<char>>,
+}
+
+impl NodePattern {
+  fn parse(text: &str) -> io::Result<NodePattern> {
+    let globs: Vec<Vec<char>> = text
+      .split(',')
+      .map(|glob| glob.trim().chars().collect())
+      .collect();
+    if globs.iter().any(|glob: &Vec<char>| glob.is_empty()) {
+      return Err(invalid_input(format!("empty node pattern in `{}`", text)));
+    }
+    Ok(NodePattern { globs })
+  }
+
+  fn matches(&self, label: &str) -> bool {
+    let label: Vec<char> = label.chars().collect();
+    self.globs.iter().any(|glob| glob_match(glob, &label))
+  }
+
+  // membership of every node of the network
+  fn select(&self, network: &Network) -> Vec<bool> {
+    network
+      .labels
+      .iter()
+      .map(|label| self.matches(label))
+      .collect()
+  }
+}
+
+fn glob_match(glob: &[char], label: &[char]) -> bool {
+  match glob.split_first() {
+    None => label.is_empty(),
+    Some(('*', rest)) => (0..=label.len()).any(|skip| glob_match(rest, &label[skip..])),
+    Some(('?', rest)) => !label.is_empty() && glob_match(rest, &label[1..]),
+    Some((c, rest)) => label.first() == Some(c) && glob_match(rest, &label[1..]),
+  }
+}
+
+// the network compiled to dense node ids (in label order), `lift[k][node]`
+// is where a walk starting at `node` on the first instruction is after 2^k
+// full passes over the instructions
+struct Network {
+  labels: Vec<String>,
+  ids: HashMap<String, usize>,
+  // 0 for left, 1 for right
+  instructions: Vec<usize>,
+  next: Vec<[usize; 2]>,
+  lift: Vec<Vec<usize>>,
+}
+
+impl Network {
+  fn load(file: &str) -> io::Result<Network> {
+    let (instructions, nodes) = build_instructions_and_nodes(file)?;
+    Network::compile(&instructions, &nodes)
+  }
+
+  fn compile(instructions: &[char], nodes: &HashMap<String, Node>) -> io::Result<Network> {
+    let mut labels: Vec<String> = nodes.keys().cloned().collect();
+    labels.sort();
+    let ids: HashMap<String, usize> = labels
+      .iter()
+      .enumerate()
+      .map(|(id, label)| (label.clone(), id))
+      .collect();
+
+    let instructions = instructions
+      .iter()
+      .map(|&ins| match ins {
+        'L' => Ok(0),
+        'R' => Ok(1),
+        _ => Err(invalid_data(format!("unknown instruction `{}`", ins))),
+      })
+      .collect::<io::Result<Vec<usize>>>()?;
+    if instructions.is_empty() {
+      return Err(invalid_data("no instructions".to_string()));
+    }
+
+    let lookup = |label: &str, from: &str, line: usize| {
+      ids.get(label).copied().ok_or_else(|| {
+        invalid_data(format!(
+          "line {}: node `{}` leads to undefined node `{}`",
+          line, from, label
+        ))
+      })
+    };
+    let next = labels
+      .iter()
+      .map(|label| {
+        let node = &nodes[label];
+        Ok([
+          lookup(&node.left, label, node.line)?,
+          lookup(&node.right, label, node.line)?,
+        ])
+      })
+      .collect::<io::Result<Vec<[usize; 2]>>>()?;
+
+    let mut network = Network {
+      labels,
+      ids,
+      instructions,
+      next,
+      lift: Vec::new(),
+    };
+    let pass: Vec<usize> = (0..network.labels.len())
+      .map(|node| network.walk(node, network.instructions.len()))
+      .collect();
+    network.lift.push(pass);
+    // a step count fits in a u64, so 64 doublings cover every pass count
+    for level in 1..64 {
+      let prev = &network.lift[level - 1];
+      let doubled = prev.iter().map(|&node| prev[node]).collect();
+      network.lift.push(doubled);
+    }
+
+    Ok(network)
+  }
+
+  fn id(&self, label: &str) -> io::Result<usize> {
+    self
+      .ids
+      .get(label)
+      .copied()
+      .ok_or_else(|| invalid_input(format!("unknown node `{}`", label)))
+  }
+
+  fn step(&self, node: usize, ins_idx: usize) -> usize {
+    self.next[node][self.instructions[ins_idx]]
+  }
+
+  // follows the first `steps` instructions, `steps` at most one pass
+  fn walk(&self, node: usize, steps: usize) -> usize {
+    (0..steps).fold(node, |node, ins_idx| self.step(node, ins_idx))
+  }
+
+  fn position_after(&self, start: usize, steps: u64) -> usize {
+    let len = self.instructions.len() as u64;
+    let mut passes = steps / len;
+    let mut node = start;
+    let mut level = 0;
+    while passes > 0 {
+      if passes & 1 == 1 {
+        node = self.lift[level][node];
+      }
+      passes >>= 1;
+      level += 1;
+    }
+    self.walk(node, (steps % len) as usize)
+  }
+
+  // for each node, the offsets inside a pass starting there at which the
+  // walk stands on a goal node
+  fn pass_hits(&self, is_goal: impl Fn(usize) -> bool) -> Vec<Vec<u64>> {
+    (0..self.labels.len())
+      .map(|start| {
+        let mut hits: Vec<u64> = Vec::new();
+        let mut node = start;
+        for ins_idx in 0..self.instructions.len() {
+          if is_goal(node) {
+            hits.push(ins_idx as u64);
+          }
+          node = self.step(node, ins_idx);
+        }
+        hits
+      })
+      .collect()
+  }
 }
 
 pub fn solve() {
@@ -14,129 +186,643 @@ pub fn solve() {
   println!("Part 2: {}", part2("inputs/day8/part1").unwrap());
 }
 
+pub fn run(args: &[String]) {
+  match args.first().map(|s| s.as_str()) {
+    None => solve(),
+    // day8 at <node> <steps> [file]
+    Some("at") => report(print_position(&args[1..])),
+    // day8 path|ghosts|dot [--start PATTERN] [--goal PATTERN] [file]
+    Some("path") => report(parse_query_args(&args[1..], "AAA", "ZZZ").and_then(print_paths)),
+    Some("ghosts") => report(parse_query_args(&args[1..], "*A", "*Z").and_then(print_ghosts)),
+    Some("dot") => report(parse_query_args(&args[1..], "*A", "*Z").and_then(print_dot)),
+    Some(cmd) => println!("Unknown day8 command: {}", cmd),
+  }
+}
+
+fn report(result: io::Result<()>) {
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
 fn part1(file: &str) -> io::Result<u64> {
-  let (instructions, nodes) = build_instructions_and_nodes(file);
+  let network = Network::load(file)?;
+  let start = network.id("AAA")?;
+  let is_goal = NodePattern::parse("ZZZ")?.select(&network);
+
+  count_steps(&network, start, &is_goal)
+    .ok_or_else(|| invalid_data("ZZZ is never reached from AAA".to_string()))
+}
+
+fn part2(file: &str) -> io::Result<u128> {
+  let network = Network::load(file)?;
+  let starts = NodePattern::parse("*A")?.select(&network);
+  let is_goal = NodePattern::parse("*Z")?.select(&network);
+  let ghosts = analyze_ghosts(&network, &starts, &is_goal);
+
+  first_simultaneous_arrival(&ghosts)?
+    .ok_or_else(|| invalid_data("ghosts never meet on Z nodes".to_string()))
+}
+
+// jumps a full pass at a time until a pass that contains the goal, a walk
+// that sees the same pass boundary twice never reaches it
+fn count_steps(network: &Network, start: usize, is_goal: &[bool]) -> Option<u64> {
+  let hits = network.pass_hits(|node| is_goal[node]);
+  let len = network.instructions.len() as u64;
+  let mut seen = vec![false; network.labels.len()];
+  let mut node = start;
+  let mut steps: u64 = 0;
+
+  while !seen[node] {
+    if let Some(&offset) = hits[node].first() {
+      return Some(steps + offset);
+    }
+    seen[node] = true;
+    node = network.lift[0][node];
+    steps += len;
+  }
+
+  None
+}
+
+// every node visited from `start` up to and including the first goal node
+fn path(network: &Network, start: usize, is_goal: &[bool]) -> Option<Vec<usize>> {
+  let steps = count_steps(network, start, is_goal)?;
+  let mut nodes: Vec<usize> = vec![start];
+  let mut node = start;
+  for step in 0..steps {
+    node = network.step(node, step as usize % network.instructions.len());
+    nodes.push(node);
+  }
+  Some(nodes)
+}
+
+// the walk of one ghost over (node, instruction index) states: the states
+// repeat after `pre_period + cycle_len` steps, looping back to `pre_period`
+#[derive(Debug)]
+struct GhostCycle {
+  start: String,
+  pre_period: u64,
+  cycle_len: u64,
+  // steps before the cycle that end on a goal node
+  pre_hits: Vec<u64>,
+  // steps inside the first lap of the cycle that end on a goal node
+  cycle_hits: Vec<u64>,
+}
 
-  let steps = count_steps(&instructions, &nodes);
+impl GhostCycle {
+  fn is_on_goal(&self, step: u128) -> bool {
+    if step < self.pre_period as u128 {
+      return self.pre_hits.contains(&(step as u64));
+    }
+    // the same step within the first lap, the hits are sorted
+    let lap = (step - self.pre_period as u128) % self.cycle_len as u128;
+    self
+      .cycle_hits
+      .binary_search(&(self.pre_period + lap as u64))
+      .is_ok()
+  }
+}
 
-  Ok(steps)
+fn analyze_ghosts(network: &Network, starts: &[bool], is_goal: &[bool]) -> Vec<GhostCycle> {
+  let hits = network.pass_hits(|node| is_goal[node]);
+  (0..network.labels.len())
+    .filter(|&node| starts[node])
+    .map(|start| analyze_ghost(network, start, &hits))
+    .collect()
 }
 
-fn part2(file: &str) -> io::Result<u64> {
-  let (instructions, nodes) = build_instructions_and_nodes(file);
-  let mut steps_count_set: HashSet<u64> = HashSet::new();
+// the state at a pass boundary is just the node, so the cycle is found on
+// the pass table and then moved back to the first repeating step
+fn analyze_ghost(network: &Network, start: usize, hits: &[Vec<u64>]) -> GhostCycle {
+  let len = network.instructions.len();
+  let mut first_seen: Vec<Option<usize>> = vec![None; network.labels.len()];
+  let mut boundaries: Vec<usize> = Vec::new();
+  let mut node = start;
+  while first_seen[node].is_none() {
+    first_seen[node] = Some(boundaries.len());
+    boundaries.push(node);
+    node = network.lift[0][node];
+  }
+  let seen = first_seen[node].unwrap();
+  let cycle_passes = boundaries.len() - seen;
+  let cycle_len = (cycle_passes * len) as u64;
+
+  // a state inside pass `seen - 1` may already repeat one cycle later
+  let mut pre_period = (seen * len) as u64;
+  if seen > 0 {
+    let mut before = boundaries[seen - 1];
+    let mut after = boundaries[seen - 1 + cycle_passes];
+    let mut trail: Vec<(usize, usize)> = Vec::with_capacity(len);
+    for ins_idx in 0..len {
+      trail.push((before, after));
+      before = network.step(before, ins_idx);
+      after = network.step(after, ins_idx);
+    }
+    for &(before, after) in trail.iter().rev() {
+      if before != after {
+        break;
+      }
+      pre_period -= 1;
+    }
+  }
 
-  for (current_node, node) in nodes.iter() {
-    if current_node.ends_with("A") {
-      let steps = count_steps_part_2(&instructions, current_node, &nodes);
-      steps_count_set.insert(steps);
+  let mut pre_hits: Vec<u64> = Vec::new();
+  let mut cycle_hits: Vec<u64> = Vec::new();
+  for (pass, &node) in boundaries.iter().enumerate() {
+    for &offset in &hits[node] {
+      let step = (pass * len) as u64 + offset;
+      if step < pre_period {
+        pre_hits.push(step);
+      } else if step < pre_period + cycle_len {
+        cycle_hits.push(step);
+      }
     }
   }
 
-  let steps = steps_count_set
+  GhostCycle {
+    start: network.labels[start].clone(),
+    pre_period,
+    cycle_len,
+    pre_hits,
+    cycle_hits,
+  }
+}
+
+// combining ghosts by CRT multiplies the number of residues by their hits,
+// past this many the remaining ghosts are checked step by step instead
+const MAX_RESIDUES: usize = 1 << 16;
+// candidate steps tried before giving up on the step by step search
+const MAX_CANDIDATES: u64 = 1 << 28;
+
+// earliest step where every ghost is on a goal node at once, if any
+fn first_simultaneous_arrival(ghosts: &[GhostCycle]) -> io::Result<Option<u128>> {
+  let Some(longest_pre_period) = ghosts.iter().map(|g| g.pre_period as u128).max() else {
+    return Ok(None);
+  };
+
+  // before every ghost is in its cycle, the ghost with the longest pre-period
+  // can only be on a goal at one of its own pre-period hits
+  let slowest = ghosts
+    .iter()
+    .find(|g| g.pre_period as u128 == longest_pre_period)
+    .unwrap();
+  let early = slowest
+    .pre_hits
     .iter()
-    .fold(1, |acc, steps| lcm(acc, *steps));
-  // println!("steps_count_set: {:?}", steps_count_set);
+    .map(|&hit| hit as u128)
+    .filter(|&step| ghosts.iter().all(|g| g.is_on_goal(step)))
+    .min();
+  if early.is_some() {
+    return Ok(early);
+  }
+
+  // afterwards the ghosts repeat together every `period` steps
+  let mut period: u128 = 1;
+  for ghost in ghosts {
+    period = crt(0, period, 0, ghost.cycle_len as u128)?.unwrap().1;
+  }
+  let end = longest_pre_period
+    .checked_add(period)
+    .ok_or_else(|| overflow("the ghosts' common period"))?;
+
+  // each ghost is on a goal exactly at its cycle hits modulo its cycle
+  // length, the ghosts with the fewest hits are combined into residues
+  // modulo `modulus` for as long as the residue set stays small
+  let mut order: Vec<&GhostCycle> = ghosts.iter().collect();
+  order.sort_by_key(|ghost| ghost.cycle_hits.len());
+  let mut residues: Vec<u128> = vec![0];
+  let mut modulus: u128 = 1;
+  let mut combined = 0;
+  for ghost in &order {
+    if residues.len() * ghost.cycle_hits.len() > MAX_RESIDUES && combined > 0 {
+      break;
+    }
+    let cycle_len = ghost.cycle_len as u128;
+    let mut next: Vec<u128> = Vec::new();
+    for &residue in &residues {
+      for &hit in &ghost.cycle_hits {
+        next.extend(crt(residue, modulus, hit as u128 % cycle_len, cycle_len)?.map(|(x, _)| x));
+      }
+    }
+    next.sort_unstable();
+    next.dedup();
+    residues = next;
+    modulus = crt(0, modulus, 0, cycle_len)?.unwrap().1;
+    combined += 1;
+  }
+  let rest = &order[combined..];
+
+  // candidates in increasing order from the longest pre-period on, one
+  // block of `modulus` steps at a time, up to a full common period
+  let mut tried: u64 = 0;
+  let mut base = longest_pre_period / modulus * modulus;
+  while base < end {
+    for &residue in &residues {
+      let step = base + residue;
+      if step < longest_pre_period {
+        continue;
+      }
+      if step >= end {
+        return Ok(None);
+      }
+      if rest.iter().all(|g| g.is_on_goal(step)) {
+        return Ok(Some(step));
+      }
+      tried += 1;
+      if tried >= MAX_CANDIDATES {
+        return Err(invalid_input(format!(
+          "gave up after {} candidate steps, the goal patterns match too many nodes",
+          tried
+        )));
+      }
+    }
+    if residues.is_empty() {
+      break;
+    }
+    base = match base.checked_add(modulus) {
+      Some(base) => base,
+      None => break,
+    };
+  }
 
-  Ok(steps)
+  Ok(None)
 }
 
-fn build_instructions_and_nodes(file: &str) -> (Vec<char>, HashMap<String, Node>) {
-  let input = fs::read_to_string(file).unwrap();
-  let mut nodes: HashMap<String, Node> = HashMap::new();
-  let mut line_iter = input.lines();
+// generalised chinese remainder theorem, moduli need not be coprime:
+// x = a1 (mod m1) and x = a2 (mod m2) has a solution modulo lcm(m1, m2)
+// only when a1 = a2 (mod gcd(m1, m2)). m2 is a cycle length, so everything
+// but the lcm is reduced below m2 before multiplying
+fn crt(a1: u128, m1: u128, a2: u128, m2: u128) -> io::Result<Option<(u128, u128)>> {
+  let m2 = m2 as i128;
+  let (g, p, _) = extended_gcd((m1 % m2 as u128) as i128, m2);
+  let g = g as u128;
+  let m2 = m2 as u128;
+  // a2 - a1 modulo m2, a multiple of g exactly when a2 - a1 is
+  let diff = (a2 % m2 + m2 - a1 % m2) % m2;
+  if !diff.is_multiple_of(g) {
+    return Ok(None);
+  }
+  let lcm = (m1 / g)
+    .checked_mul(m2)
+    .ok_or_else(|| overflow("the ghosts' common period"))?;
+  // x = a1 + m1 * k where k = (diff / g) * p (mod m2 / g), both factors are
+  // below m2 / g so their product fits
+  let m2_g = m2 / g;
+  let p = (p % m2_g as i128).rem_euclid(m2_g as i128) as u128;
+  let k = (diff / g % m2_g) * p % m2_g;
+  // a1 + m1 * k stays below m1 * (m2 / g) = lcm
+  let x = m1
+    .checked_mul(k)
+    .and_then(|offset| offset.checked_add(a1 % m1))
+    .ok_or_else(|| overflow("the ghosts' common period"))?;
+  Ok(Some((x % lcm, lcm)))
+}
 
-  // first line is instructions
-  let instructions = line_iter.next().unwrap().chars().collect::<Vec<char>>();
-  line_iter.next(); // skip the empty line
-  while let Some(line) = line_iter.next() {
-    let mut split = line.split("=");
-    let label = split.next().expect("Missing label").trim().to_string();
-    let neighbours_str = split.next().expect("Missing neighbours");
-    let neighbours_str = neighbours_str.trim();
-    let neighbours_str = neighbours_str.trim_matches('(').trim_matches(')');
-    let mut neighbours = neighbours_str.split(',');
-
-    let left = neighbours
-      .next()
-      .expect("Missing left neighbour")
-      .trim()
-      .to_string();
-    let right = neighbours
-      .next()
-      .expect("Missing right neighbour")
-      .trim()
-      .to_string();
-    let node = Node { left, right };
-    nodes.insert(label, node);
+// returns (g, x, y) with a * x + b * y = g
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+  if b == 0 {
+    return (a, 1, 0);
   }
+  let (g, x, y) = extended_gcd(b, a % b);
+  (g, y, x - (a / b) * y)
+}
 
-  (instructions, nodes)
+struct Query {
+  starts: NodePattern,
+  goals: NodePattern,
+  file: String,
 }
 
-fn count_steps(instructions: &Vec<char>, nodes: &HashMap<String, Node>) -> u64 {
-  let mut steps: u64 = 0;
-  let mut current_node = "AAA";
-  let mut ins_idx = 0;
-  while current_node != "ZZZ" {
-    if instructions[ins_idx] == 'L' {
-      current_node = &nodes
-        .get(current_node)
-        .expect("Missing {current_node} node")
-        .left;
-    } else {
-      current_node = &nodes
-        .get(current_node)
-        .expect("Missing {current_node} node")
-        .right;
+fn parse_query_args(args: &[String], starts: &str, goals: &str) -> io::Result<Query> {
+  let mut query = Query {
+    starts: NodePattern::parse(starts)?,
+    goals: NodePattern::parse(goals)?,
+    file: String::from("inputs/day8/part1"),
+  };
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    let mut value = || {
+      iter
+        .next()
+        .ok_or_else(|| invalid_input(format!("missing value for {}", arg)))
+    };
+    match arg.as_str() {
+      "--start" => query.starts = NodePattern::parse(value()?)?,
+      "--goal" => query.goals = NodePattern::parse(value()?)?,
+      _ => query.file = arg.clone(),
     }
-    steps += 1;
-    ins_idx += 1;
-    if ins_idx >= instructions.len() {
-      ins_idx = 0;
+  }
+
+  Ok(query)
+}
+
+fn print_paths(query: Query) -> io::Result<()> {
+  let network = Network::load(&query.file)?;
+  let is_goal = query.goals.select(&network);
+  let starts = query.starts.select(&network);
+
+  for start in (0..network.labels.len()).filter(|&node| starts[node]) {
+    match path(&network, start, &is_goal) {
+      Some(nodes) => {
+        println!("# {}: {} steps", network.labels[start], nodes.len() - 1);
+        for node in nodes {
+          println!("{}", network.labels[node]);
+        }
+      }
+      None => println!("# {}: never reaches a goal", network.labels[start]),
     }
   }
+  Ok(())
+}
+
+fn print_ghosts(query: Query) -> io::Result<()> {
+  let network = Network::load(&query.file)?;
+  let starts = query.starts.select(&network);
+  let is_goal = query.goals.select(&network);
+  let ghosts = analyze_ghosts(&network, &starts, &is_goal);
 
-  steps
+  for ghost in &ghosts {
+    println!(
+      "{}: pre-period {}, cycle length {}, goals before cycle {:?}, goals in cycle {:?}",
+      ghost.start, ghost.pre_period, ghost.cycle_len, ghost.pre_hits, ghost.cycle_hits
+    );
+  }
+  match first_simultaneous_arrival(&ghosts)? {
+    Some(steps) => println!("All ghosts are on goal nodes after {} steps", steps),
+    None => println!("The ghosts are never on goal nodes at the same time"),
+  }
+  Ok(())
 }
 
-fn count_steps_part_2(
-  instructions: &Vec<char>,
-  current_node: &str,
-  nodes: &HashMap<String, Node>,
-) -> u64 {
-  let mut steps: u64 = 0;
-  let mut current_node = current_node;
-  let mut ins_idx = 0;
-  while !current_node.ends_with("Z") {
-    if instructions[ins_idx] == 'L' {
-      current_node = &nodes
-        .get(current_node)
-        .expect("Missing {current_node} node")
-        .left;
+// start nodes are boxes and goal nodes double circles, the nodes and edges
+// the ghosts keep walking once in their cycle are drawn in red
+fn print_dot(query: Query) -> io::Result<()> {
+  let network = Network::load(&query.file)?;
+  let starts = query.starts.select(&network);
+  let is_goal = query.goals.select(&network);
+  let ghosts = analyze_ghosts(&network, &starts, &is_goal);
+  let len = network.instructions.len() as u64;
+
+  let mut cycle_nodes = vec![false; network.labels.len()];
+  let mut cycle_edges = vec![[false; 2]; network.labels.len()];
+  for ghost in &ghosts {
+    let mut node = network.position_after(network.id(&ghost.start)?, ghost.pre_period);
+    for step in ghost.pre_period..ghost.pre_period + ghost.cycle_len {
+      let ins_idx = (step % len) as usize;
+      cycle_nodes[node] = true;
+      cycle_edges[node][network.instructions[ins_idx]] = true;
+      node = network.step(node, ins_idx);
+    }
+  }
+
+  println!("digraph network {{");
+  for (node, label) in network.labels.iter().enumerate() {
+    let mut attrs: Vec<&str> = Vec::new();
+    if starts[node] {
+      attrs.push("shape=box");
+    } else if is_goal[node] {
+      attrs.push("shape=doublecircle");
+    }
+    if cycle_nodes[node] {
+      attrs.push("color=red");
+    }
+    if attrs.is_empty() {
+      println!("  \"{}\";", label);
     } else {
-      current_node = &nodes
-        .get(current_node)
-        .expect("Missing {current_node} node")
-        .right;
+      println!("  \"{}\" [{}];", label, attrs.join(", "));
     }
-    steps += 1;
-    ins_idx += 1;
-    if ins_idx >= instructions.len() {
-      ins_idx = 0;
+  }
+  for (node, label) in network.labels.iter().enumerate() {
+    let [left, right] = network.next[node];
+    let edges: Vec<(usize, &str, bool)> = if left == right {
+      vec![(left, "LR", cycle_edges[node][0] || cycle_edges[node][1])]
+    } else {
+      vec![
+        (left, "L", cycle_edges[node][0]),
+        (right, "R", cycle_edges[node][1]),
+      ]
+    };
+    for (target, direction, on_cycle) in edges {
+      println!(
+        "  \"{}\" -> \"{}\" [label=\"{}\"{}];",
+        label,
+        network.labels[target],
+        direction,
+        if on_cycle {
+          ", color=red, penwidth=2"
+        } else {
+          ""
+        }
+      );
     }
   }
+  println!("}}");
+  Ok(())
+}
+
+fn print_position(args: &[String]) -> io::Result<()> {
+  let (start, steps) = match args {
+    [start, steps, ..] => (start, steps),
+    _ => return Err(invalid_input(String::from("expected <node> <steps>"))),
+  };
+  let steps = steps
+    .parse::<u64>()
+    .map_err(|_| invalid_input(format!("malformed step count `{}`", steps)))?;
+  let file = args.get(2).map_or("inputs/day8/part1", |s| s.as_str());
 
-  steps
+  let network = Network::load(file)?;
+  let node = network.position_after(network.id(start)?, steps);
+  println!(
+    "After {} steps from {}: {}",
+    steps, start, network.labels[node]
+  );
+  Ok(())
 }
 
-fn gcd(a: u64, b: u64) -> u64 {
-  if b == 0 {
-    return a;
+fn build_instructions_and_nodes(file: &str) -> io::Result<(Vec<char>, HashMap<String, Node>)> {
+  let input = fs::read_to_string(file)?;
+  parse_instructions_and_nodes(&input)
+}
+
+fn parse_instructions_and_nodes(input: &str) -> io::Result<(Vec<char>, HashMap<String, Node>)> {
+  let mut nodes: HashMap<String, Node> = HashMap::new();
+  let mut line_iter = input.lines().enumerate();
+
+  // first line is instructions
+  let (_, instructions) = line_iter
+    .next()
+    .ok_or_else(|| invalid_data("missing instructions".to_string()))?;
+  let instructions = instructions.trim().chars().collect::<Vec<char>>();
+  line_iter.next(); // skip the empty line
+  for (idx, line) in line_iter {
+    let line_no = idx + 1;
+    let malformed = || invalid_data(format!("line {}: malformed node `{}`", line_no, line));
+    let (label, neighbours) = line.split_once('=').ok_or_else(malformed)?;
+    let (left, right) = neighbours
+      .trim()
+      .strip_prefix('(')
+      .and_then(|n| n.strip_suffix(')'))
+      .and_then(|n| n.split_once(','))
+      .ok_or_else(malformed)?;
+
+    let label = label.trim().to_string();
+    let node = Node {
+      left: left.trim().to_string(),
+      right: right.trim().to_string(),
+      line: line_no,
+    };
+    if let Some(previous) = nodes.insert(label.clone(), node) {
+      return Err(invalid_data(format!(
+        "line {}: node `{}` is already defined on line {}",
+        line_no, label, previous.line
+      )));
+    }
   }
-  gcd(b, a % b)
+
+  Ok((instructions, nodes))
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn overflow(what: &str) -> io::Error {
+  invalid_data(format!("{} overflows u128", what))
 }
 
-fn lcm(a: u64, b: u64) -> u64 {
-  a * b / gcd(a, b)
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ghost(pre_period: u64, cycle_len: u64, pre_hits: &[u64], cycle_hits: &[u64]) -> GhostCycle {
+    GhostCycle {
+      start: String::new(),
+      pre_period,
+      cycle_len,
+      pre_hits: pre_hits.to_vec(),
+      cycle_hits: cycle_hits.to_vec(),
+    }
+  }
+
+  fn brute_force(ghosts: &[GhostCycle], limit: u128) -> Option<u128> {
+    (0..limit).find(|&step| ghosts.iter().all(|g| g.is_on_goal(step)))
+  }
+
+  fn compile(input: &str) -> Network {
+    let (instructions, nodes) = parse_instructions_and_nodes(input).unwrap();
+    Network::compile(&instructions, &nodes).unwrap()
+  }
+
+  const GHOSTS: &str = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+  #[test]
+  fn test_position_after_matches_walk() {
+    let network = compile(
+      "LLRLR
+
+AAA = (BBB, CCC)
+BBB = (DDD, AAA)
+CCC = (ZZZ, BBB)
+DDD = (CCC, DDD)
+ZZZ = (AAA, ZZZ)",
+    );
+    for start in 0..network.labels.len() {
+      let mut node = start;
+      for steps in 0..200 {
+        assert_eq!(network.position_after(start, steps), node);
+        node = network.step(node, steps as usize % network.instructions.len());
+      }
+    }
+  }
+
+  #[test]
+  fn test_ghosts_on_sample() {
+    let network = compile(GHOSTS);
+    let starts = NodePattern::parse("*A").unwrap().select(&network);
+    let is_goal = NodePattern::parse("*Z").unwrap().select(&network);
+    let ghosts = analyze_ghosts(&network, &starts, &is_goal);
+    assert_eq!(ghosts.len(), 2);
+    assert_eq!(first_simultaneous_arrival(&ghosts).unwrap(), Some(6));
+  }
+
+  #[test]
+  fn test_path_with_patterns() {
+    let network = compile(GHOSTS);
+    let is_goal = NodePattern::parse("2?Z, XX*").unwrap().select(&network);
+    let labels = |start: &str| {
+      path(&network, network.id(start).unwrap(), &is_goal).map(|nodes| {
+        nodes
+          .iter()
+          .map(|&n| network.labels[n].as_str())
+          .collect::<Vec<&str>>()
+      })
+    };
+    assert_eq!(labels("22A"), Some(vec!["22A", "22B", "22C", "22Z"]));
+    assert_eq!(labels("11A"), None);
+    assert_eq!(labels("XXX"), Some(vec!["XXX"]));
+  }
+
+  #[test]
+  fn test_missing_node_is_an_error() {
+    let (instructions, nodes) = parse_instructions_and_nodes("L\n\nAAA = (BBB, AAA)").unwrap();
+    let err = Network::compile(&instructions, &nodes).err().unwrap();
+    assert_eq!(
+      err.to_string(),
+      "line 3: node `AAA` leads to undefined node `BBB`"
+    );
+    assert!(parse_instructions_and_nodes("L\n\nAAA = BBB, AAA").is_err());
+  }
+
+  #[test]
+  fn test_first_simultaneous_arrival_matches_brute_force() {
+    let cases = vec![
+      vec![ghost(1, 2, &[], &[2]), ghost(1, 6, &[], &[3, 6])],
+      vec![ghost(3, 4, &[1], &[5]), ghost(2, 6, &[1], &[4, 7])],
+      vec![ghost(0, 4, &[], &[1]), ghost(0, 6, &[], &[3])],
+      vec![ghost(5, 9, &[2, 4], &[6, 11]), ghost(1, 15, &[0], &[7, 12])],
+      vec![ghost(0, 4, &[], &[0]), ghost(0, 6, &[], &[1])],
+    ];
+    for ghosts in cases {
+      assert_eq!(
+        first_simultaneous_arrival(&ghosts).unwrap(),
+        brute_force(&ghosts, 1000)
+      );
+    }
+  }
+
+  #[test]
+  fn test_dense_goals_are_searched_step_by_step() {
+    // too many hits to combine every ghost, the rest are checked per step
+    let dense = |pre_period: u64, cycle_len: u64, seed: u64| {
+      let hits: Vec<u64> = (pre_period..pre_period + cycle_len)
+        .filter(|step| (step * 7919 + seed) % 13 < 9)
+        .collect();
+      ghost(pre_period, cycle_len, &[], &hits)
+    };
+    let ghosts = vec![dense(2, 400, 1), dense(3, 450, 5), dense(1, 500, 8)];
+    assert!(ghosts.iter().all(|g| g.cycle_hits.len() > 256));
+    let expected = brute_force(&ghosts, 20000);
+    assert!(expected.is_some());
+    assert_eq!(first_simultaneous_arrival(&ghosts).unwrap(), expected);
+
+    // three cycles close to 2^64 have no common period within u128
+    let huge = [u64::MAX, u64::MAX - 2, u64::MAX - 4];
+    let ghosts: Vec<GhostCycle> = huge.iter().map(|&len| ghost(0, len, &[], &[1])).collect();
+    assert!(first_simultaneous_arrival(&ghosts).is_err());
+  }
 }
@@ -1,85 +1,398 @@
+use num_bigint::{BigInt, Sign};
 use std::fs;
 use std::io;
 
+// newton forward form f(x) = sum of differences[j] * C(x, j), where x is
+// the index into the history and differences[j] the first value of the
+// j-th difference row
+struct SequenceModel {
+  len: usize,
+  differences: Vec<BigInt>,
+  // whether differencing reached a row of zeros, otherwise the degree is
+  // only the highest one the history can pin down
+  terminated: bool,
+}
+
+impl SequenceModel {
+  fn fit(values: &[i64]) -> SequenceModel {
+    let mut row: Vec<BigInt> = values.iter().map(|&v| BigInt::from(v)).collect();
+    let mut differences: Vec<BigInt> = Vec::new();
+    let mut terminated = false;
+
+    while !row.is_empty() {
+      if row.iter().all(|v| v.sign() == Sign::NoSign) {
+        terminated = true;
+        break;
+      }
+      differences.push(row[0].clone());
+      row = row.windows(2).map(|w| &w[1] - &w[0]).collect();
+    }
+
+    SequenceModel {
+      len: values.len(),
+      differences,
+      terminated,
+    }
+  }
+
+  fn degree(&self) -> usize {
+    self.differences.len().saturating_sub(1)
+  }
+
+  fn at(&self, x: &BigInt) -> BigInt {
+    let mut value = BigInt::from(0);
+    // C(x, j) stays an integer for negative x too, and each division by j
+    // is exact because j consecutive integers have been multiplied in
+    let mut binomial = BigInt::from(1);
+    for (j, difference) in self.differences.iter().enumerate() {
+      if j > 0 {
+        binomial = binomial * (x - (j - 1)) / j;
+      }
+      value += difference * &binomial;
+    }
+    value
+  }
+
+  // k steps after the last value of the history
+  fn ahead(&self, k: &BigInt) -> BigInt {
+    self.at(&(k + (self.len - 1)))
+  }
+
+  // k steps before the first value of the history
+  fn behind(&self, k: &BigInt) -> BigInt {
+    self.at(&-k)
+  }
+}
+
 pub fn solve() {
   println!("Part 1: {}", part1("inputs/day9/part1").unwrap());
   println!("Part 2: {}", part2("inputs/day9/part1").unwrap());
 }
 
+pub fn run(args: &[String]) {
+  let result = match args.first().map(|s| s.as_str()) {
+    None => {
+      solve();
+      Ok(())
+    }
+    Some("extrapolate") => print_extrapolation(&args[1..]),
+    Some("diagnostics") => print_diagnostics(&args[1..]),
+    Some(cmd) => {
+      println!("Unknown day9 command: {}", cmd);
+      Ok(())
+    }
+  };
+
+  if let Err(err) = result {
+    println!("Error: {}", err);
+  }
+}
+
 fn part1(file: &str) -> io::Result<i64> {
-  let input = fs::read_to_string(file)?;
   let mut total: i64 = 0;
 
-  for line in input.lines() {
-    let numbers: Vec<i64> = line
-      .split_whitespace()
-      .map(|s| s.parse::<i64>().unwrap())
-      .collect();
-    let mut next_number = get_next_number(&numbers);
-    total += next_number;
+  for (idx, numbers) in parse_histories(file)?.iter().enumerate() {
+    let next_number = Pyramid::build(numbers, idx + 1)?.next()?;
+    total = total
+      .checked_add(next_number)
+      .ok_or_else(|| invalid_data("total overflows i64".to_string()))?;
   }
 
   Ok(total)
 }
 
 fn part2(file: &str) -> io::Result<i64> {
-  let input = fs::read_to_string(file)?;
   let mut total: i64 = 0;
 
-  for line in input.lines() {
-    let numbers: Vec<i64> = line
-      .split_whitespace()
-      .map(|s| s.parse::<i64>().unwrap())
-      .collect();
-    let mut prev_number = get_prev_number(&numbers);
-    total += prev_number;
+  for (idx, numbers) in parse_histories(file)?.iter().enumerate() {
+    let prev_number = Pyramid::build(numbers, idx + 1)?.prev()?;
+    total = total
+      .checked_add(prev_number)
+      .ok_or_else(|| invalid_data("total overflows i64".to_string()))?;
   }
 
   Ok(total)
 }
 
-fn get_next_number(numbers: &Vec<i64>) -> i64 {
-  let mut len = numbers.len();
-  let mut diffs = numbers.clone();
-  let mut is_all_zero = false;
-  while !is_all_zero {
-    is_all_zero = true;
-    for idx in 0..len - 1 {
-      let diff = diffs[idx + 1] - diffs[idx];
-      if diff != 0 {
-        is_all_zero = false;
+// the history on level 0 and its successive differences below, down to a
+// row of zeros when `terminated`, otherwise down to a single value
+struct Pyramid {
+  line: usize,
+  rows: Vec<Vec<i64>>,
+  terminated: bool,
+}
+
+impl Pyramid {
+  fn build(numbers: &[i64], line: usize) -> io::Result<Pyramid> {
+    if numbers.is_empty() {
+      return Err(invalid_data(format!("line {}: empty history", line)));
+    }
+    let mut rows: Vec<Vec<i64>> = vec![numbers.to_vec()];
+
+    let terminated = loop {
+      let row = rows.last().unwrap();
+      if row.iter().all(|&v| v == 0) {
+        break true;
+      }
+      if row.len() == 1 {
+        break false;
       }
-      diffs[idx] = diff;
+      let level = rows.len();
+      let diffs = row
+        .windows(2)
+        .map(|w| {
+          w[1]
+            .checked_sub(w[0])
+            .ok_or_else(|| overflow(line, level, format!("{} - {}", w[1], w[0])))
+        })
+        .collect::<io::Result<Vec<i64>>>()?;
+      rows.push(diffs);
+    };
+
+    Ok(Pyramid {
+      line,
+      rows,
+      terminated,
+    })
+  }
+
+  // number of difference rows below the history
+  fn depth(&self) -> usize {
+    self.rows.len() - 1
+  }
+
+  fn next(&self) -> io::Result<i64> {
+    let mut next_number: i64 = 0;
+    for (level, row) in self.rows.iter().enumerate().rev() {
+      let last = row[row.len() - 1];
+      next_number = last
+        .checked_add(next_number)
+        .ok_or_else(|| overflow(self.line, level, format!("{} + {}", last, next_number)))?;
     }
-    len -= 1;
+    Ok(next_number)
   }
 
-  diffs.iter().sum()
+  fn prev(&self) -> io::Result<i64> {
+    let mut prev_number: i64 = 0;
+    for (level, row) in self.rows.iter().enumerate().rev() {
+      prev_number = row[0]
+        .checked_sub(prev_number)
+        .ok_or_else(|| overflow(self.line, level, format!("{} - {}", row[0], prev_number)))?;
+    }
+    Ok(prev_number)
+  }
 }
 
-fn get_prev_number(numbers: &Vec<i64>) -> i64 {
-  let mut len = numbers.len();
-  let mut diffs = numbers.clone();
-  let mut first_numbers: Vec<i64> = vec![numbers[0]];
-  let mut is_all_zero = false;
-  while !is_all_zero {
-    is_all_zero = true;
-    for idx in 0..len - 1 {
-      let diff = diffs[idx + 1] - diffs[idx];
-      if diff != 0 {
-        is_all_zero = false;
-      }
-      diffs[idx] = diff;
-      if idx == 0 {
-        first_numbers.push(diffs[idx]);
+fn overflow(line: usize, level: usize, operation: String) -> io::Error {
+  invalid_data(format!(
+    "line {}: i64 overflow on difference level {} computing {}",
+    line, level, operation
+  ))
+}
+
+fn parse_histories(file: &str) -> io::Result<Vec<Vec<i64>>> {
+  let input = fs::read_to_string(file)?;
+  input
+    .lines()
+    .enumerate()
+    .map(|(idx, line)| {
+      line
+        .split_whitespace()
+        .map(|s| {
+          s.parse::<i64>()
+            .map_err(|_| invalid_data(format!("line {}: malformed number `{}`", idx + 1, s)))
+        })
+        .collect()
+    })
+    .collect()
+}
+
+// <k> [file], k steps ahead of the last value or, when negative, -k steps
+// before the first one
+fn print_extrapolation(args: &[String]) -> io::Result<()> {
+  let offset = args
+    .first()
+    .ok_or_else(|| invalid_input(String::from("missing offset")))?;
+  let offset = &offset
+    .parse::<BigInt>()
+    .map_err(|_| invalid_input(format!("malformed offset `{}`", offset)))?;
+  let file = args.get(1).map_or("inputs/day9/part1", |s| s.as_str());
+  let mut total = BigInt::from(0);
+
+  for (idx, numbers) in parse_histories(file)?.iter().enumerate() {
+    let model = SequenceModel::fit(numbers);
+    let value = if offset.sign() == Sign::Minus {
+      model.behind(&-offset)
+    } else {
+      model.ahead(offset)
+    };
+
+    println!(
+      "line {}: degree {}, value {}",
+      idx + 1,
+      model.degree(),
+      value
+    );
+    if !model.terminated {
+      println!(
+        "warning: line {} never reaches a row of zeros, degree {} is only an upper bound",
+        idx + 1,
+        model.degree()
+      );
+    }
+    total += value;
+  }
+
+  println!("Total: {}", total);
+  Ok(())
+}
+
+// [--format table|csv] [file]
+fn print_diagnostics(args: &[String]) -> io::Result<()> {
+  let mut csv = false;
+  let mut file = "inputs/day9/part1";
+  let mut iter = args.iter();
+
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "--format" => {
+        let format = iter
+          .next()
+          .ok_or_else(|| invalid_input(format!("missing value for {}", arg)))?;
+        csv = match format.as_str() {
+          "table" => false,
+          "csv" => true,
+          _ => return Err(invalid_input(format!("unknown format `{}`", format))),
+        }
       }
+      _ => file = arg,
+    }
+  }
+
+  let histories = parse_histories(file)?;
+
+  if csv {
+    println!("line,values,depth,terminated,next,previous,error");
+  } else {
+    println!(
+      "{:>6} {:>6} {:>6} {:>10} {:>20} {:>20}",
+      "line", "values", "depth", "terminated", "next", "previous"
+    );
+  }
+  // a line that overflows gets an error row, the others are still reported
+  for (idx, numbers) in histories.iter().enumerate() {
+    let line = idx + 1;
+    let analysis = Pyramid::build(numbers, line)
+      .and_then(|pyramid| Ok((pyramid.next()?, pyramid.prev()?, pyramid)));
+    match (analysis, csv) {
+      (Ok((next_number, prev_number, pyramid)), true) => println!(
+        "{},{},{},{},{},{},",
+        line,
+        numbers.len(),
+        pyramid.depth(),
+        pyramid.terminated,
+        next_number,
+        prev_number
+      ),
+      (Ok((next_number, prev_number, pyramid)), false) => println!(
+        "{:>6} {:>6} {:>6} {:>10} {:>20} {:>20}",
+        line,
+        numbers.len(),
+        pyramid.depth(),
+        pyramid.terminated,
+        next_number,
+        prev_number
+      ),
+      (Err(err), true) => println!(
+        "{},{},,,,,\"{}\"",
+        line,
+        numbers.len(),
+        err.to_string().replace('"', "\"\"")
+      ),
+      (Err(err), false) => println!("{:>6} {:>6} error: {}", line, numbers.len(), err),
     }
-    len -= 1;
   }
 
-  let mut prev_number: i64 = 0;
-  for num in first_numbers.iter().rev() {
-    prev_number = num - prev_number;
+  Ok(())
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_model_matches_differencing() {
+    let histories: Vec<Vec<i64>> = vec![
+      vec![0, 3, 6, 9, 12, 15],
+      vec![1, 3, 6, 10, 15, 21],
+      vec![10, 13, 16, 21, 30, 45],
+      vec![5, 5, 5],
+      vec![0, 0, 0],
+    ];
+    for history in histories {
+      let model = SequenceModel::fit(&history);
+      let pyramid = Pyramid::build(&history, 1).unwrap();
+      assert!(model.terminated && pyramid.terminated);
+      let one = BigInt::from(1);
+      assert_eq!(model.ahead(&one), BigInt::from(pyramid.next().unwrap()));
+      assert_eq!(model.behind(&one), BigInt::from(pyramid.prev().unwrap()));
+    }
+  }
+
+  #[test]
+  fn test_pyramid_overflow_names_line_and_level() {
+    let pyramid = Pyramid::build(&[0, 1, 3], 4).unwrap();
+    assert_eq!(pyramid.depth(), 2);
+    assert!(!pyramid.terminated);
+
+    let err = Pyramid::build(&[0, i64::MAX, 0], 7).err().unwrap();
+    assert_eq!(
+      err.to_string(),
+      format!(
+        "line 7: i64 overflow on difference level 2 computing {} - {}",
+        -i64::MAX,
+        i64::MAX
+      )
+    );
+
+    let pyramid = Pyramid::build(&[i64::MAX - 1, i64::MAX], 2).unwrap();
+    assert!(pyramid.next().is_err());
+    assert_eq!(pyramid.prev().unwrap(), i64::MAX - 2);
+  }
+
+  #[test]
+  fn test_model_far_offsets() {
+    // x^3 - 2x sampled at x = -2..=3
+    let cubes: Vec<i64> = (-2..=3).map(|x: i64| x.pow(3) - 2 * x).collect();
+    let model = SequenceModel::fit(&cubes);
+    assert_eq!(model.degree(), 3);
+    assert!(model.terminated);
+
+    let x = BigInt::from(3_000_000);
+    let expected = &x * &x * &x - 2 * &x;
+    assert_eq!(model.ahead(&BigInt::from(2_999_997)), expected);
+    let x = BigInt::from(-1_000_000);
+    let expected = &x * &x * &x - 2 * &x;
+    assert_eq!(model.behind(&BigInt::from(999_998)), expected);
+
+    let model = SequenceModel::fit(&[1, 2, 4]);
+    assert!(!model.terminated);
+    assert_eq!(model.degree(), 2);
+  }
+
+  #[test]
+  fn test_unknown_format() {
+    let args = |format: &str| vec![String::from("--format"), String::from(format)];
+    let err = print_diagnostics(&args("json")).unwrap_err();
+    assert_eq!(err.to_string(), "unknown format `json`");
+    assert!(print_diagnostics(&[String::from("--format")]).is_err());
   }
-  prev_number
 }
@@ -1,3 +1,4 @@
+mod bench;
 mod day1;
 mod day10;
 mod day11;
@@ -27,18 +28,18 @@ mod day9;
 fn main() {
   let args: Vec<String> = std::env::args().collect();
   match args.get(1).map(|s| s.as_str()) {
-    Some("day1") => day1::solve(),
-    Some("day2") => day2::solve(),
-    Some("day3") => day3::solve(),
-    Some("day4") => day4::solve(),
-    Some("day5") => day5::solve(),
-    Some("day6") => day6::solve(),
-    Some("day7") => day7::solve(),
-    Some("day8") => day8::solve(),
-    Some("day9") => day9::solve(),
-    Some("day10") => day10::solve(),
-    Some("day11") => day11::solve(),
-    Some("day12") => day12::solve(),
+    Some("day1") => day1::run(&args[2..]),
+    Some("day2") => day2::run(&args[2..]),
+    Some("day3") => day3::run(&args[2..]),
+    Some("day4") => day4::run(&args[2..]),
+    Some("day5") => day5::run(&args[2..]),
+    Some("day6") => day6::run(&args[2..]),
+    Some("day7") => day7::run(&args[2..]),
+    Some("day8") => day8::run(&args[2..]),
+    Some("day9") => day9::run(&args[2..]),
+    Some("day10") => day10::run(&args[2..]),
+    Some("day11") => day11::run(&args[2..]),
+    Some("day12") => day12::run(&args[2..]),
     Some("day13") => day13::solve(),
     Some("day14") => day14::solve(),
     Some("day15") => day15::solve(),